@@ -4,12 +4,14 @@ import { useState, useEffect, useRef } from 'react';
 import { v4 as uuidv4 } from 'uuid';
 import { Cog6ToothIcon } from '@heroicons/react/24/outline';
 import { loadApiKeys, loadColumns, saveColumns, getPromptById, getModelConfigById, getProjectById, getActiveProjectId, setActiveProjectId, loadProjects, getPromptsByProjectId, getModelConfigsByProjectId, getDataSetById, getDataSetsByProjectId, saveProject } from '@/lib/storage';
-import { ApiKeys, AIOutput, Prompt, ProjectModelConfig, Project, DataSet, Provider } from '@/lib/types';
+import { ApiKeys, AIOutput, Prompt, ProjectModelConfig, Project, DataSet, Provider, EvaluationRun } from '@/lib/types';
 import { PROVIDERS, getDefaultModel, ProviderConfig, isLocalProvider } from '@/lib/config';
 import { fetchOpenRouterModels, fetchProviderModels, getOpenAIModels, getAnthropicModels, getPopularOpenRouterModels, getGeminiModels } from '@/lib/fetch-models';
 import { trackEvent } from '@/lib/analytics';
 import { apiClient, isApiError } from '@/lib/api';
 import { isImageModel } from '@/lib/model-utils';
+import { isTauriEnvironment } from '@/lib/environment';
+import { createEvaluationRun, finishEvaluationRun, persistEvaluationRun, RunCancellation } from '@/lib/evaluation-runs';
 import TwoColumnLayout from '@/components/layout/two-column-layout';
 import Sidebar from '@/components/collections/sidebar';
 import ResponsePanel from '@/components/response/response-panel';
@@ -63,6 +65,8 @@ export default function Home() {
   const [generatingConfigs, setGeneratingConfigs] = useState<Record<string, boolean>>({});
   const [selectedVersions, setSelectedVersions] = useState<Record<string, string>>({});
   const [selectedDataSetId, setSelectedDataSetId] = useState<string | null>(null);
+  // Calls in flight of the current save-and-refresh run (desktop only can cancel them)
+  const runCancellationRef = useRef<RunCancellation | null>(null);
 
   // Initialize on mount
   useEffect(() => {
@@ -433,6 +437,17 @@ export default function Home() {
     // Filter configs that have API keys (or are local providers)
     const configsWithKeys = modelConfigs.filter(config => isProviderReady(apiKeys, config.provider));
 
+    // Get the selected version for a config
+    const versionFor = (config: ProjectModelConfig) => {
+      const selectedVersionId = selectedVersions[config.id];
+      if (selectedVersionId === 'latest' || !selectedVersionId) {
+        // Use latest version
+        return latestVersion;
+      }
+      // Use specific version
+      return prompt.versions.find(v => v.id === selectedVersionId) || latestVersion;
+    };
+
     // Set ALL generating states at once BEFORE starting any async operations
     // This prevents race conditions where some configs show "save and refresh" during generation
     const initialGeneratingState: Record<string, boolean> = {};
@@ -444,23 +459,33 @@ export default function Home() {
     setGeneratingConfigs(prev => ({ ...prev, ...initialGeneratingState }));
     setConfigResponses(prev => ({ ...prev, ...initialResponsesState }));
 
+    // Record one evaluation run per prompt version, saved as running before any call
+    const runs = new Map<string, EvaluationRun>();
+    configsWithKeys.forEach(config => {
+      const versionId = versionFor(config)?.id;
+      if (!versionId) return;
+      const run = runs.get(versionId) || createEvaluationRun({
+        projectId: activeProjectId,
+        promptId: prompt.id,
+        promptVersionId: versionId,
+        modelConfigIds: [],
+        dataSetId: dataSet?.id,
+      });
+      run.modelConfigIds.push(config.id);
+      runs.set(versionId, run);
+    });
+    await Promise.all(Array.from(runs.values()).map(persistEvaluationRun));
+
+    const cancellation = new RunCancellation();
+    runCancellationRef.current = cancellation;
+
     // Run inference for each model config in parallel
     const configPromises = configsWithKeys.map(async (config) => {
       const apiKey = getApiKey(apiKeys, config.provider);
       // For cloud providers, apiKey is required; for local providers, it's undefined
 
-      // Get the selected version for this config
-      const selectedVersionId = selectedVersions[config.id];
-      let selectedVersion;
-
-      if (selectedVersionId === 'latest' || !selectedVersionId) {
-        // Use latest version
-        selectedVersion = latestVersion;
-      } else {
-        // Use specific version
-        selectedVersion = prompt.versions.find(v => v.id === selectedVersionId) || latestVersion;
-      }
-
+      const selectedVersion = versionFor(config);
+      const run = selectedVersion ? runs.get(selectedVersion.id) : undefined;
       const basePromptContent = selectedVersion?.content || '';
 
       // Detect if this is an image generation model
@@ -470,6 +495,21 @@ export default function Home() {
       const itemPromises = dataSetItems.map(async (item, itemIndex) => {
         const promptContent = dataSet ? substituteVariables(basePromptContent, item.variables) : basePromptContent;
 
+        // Record a settled call in the run, unless it only failed because the run was cancelled
+        const recordResult = (output: AIOutput) => {
+          if (!run || (cancellation.cancelled && output.error)) return;
+          run.results.push({
+            id: uuidv4(),
+            modelConfigId: config.id,
+            dataSetItemId: item.id || undefined,
+            output,
+            error: output.error,
+          });
+        };
+
+        if (cancellation.cancelled) return;
+        const requestId = isImage ? undefined : cancellation.start();
+
         try {
           const data = isImage
             ? await apiClient.generateImage({
@@ -483,6 +523,7 @@ export default function Home() {
                 provider: config.provider,
                 model: config.model,
                 apiKey: apiKey,
+                requestId,
                 ...config.parameters,
               });
 
@@ -519,6 +560,7 @@ export default function Home() {
           } else {
             return; // Skip if no valid response
           }
+          recordResult(newResponse);
 
           // Track successful generation
           trackEvent('generation_success', {
@@ -551,6 +593,7 @@ export default function Home() {
             error: error.message || 'Network error',
             timestamp: Date.now(),
           };
+          recordResult(errorResponse);
 
           // Update UI immediately with error response at the correct index
           setConfigResponses(prev => {
@@ -561,6 +604,8 @@ export default function Home() {
               [config.id]: currentResponses
             };
           });
+        } finally {
+          if (requestId) cancellation.finish(requestId);
         }
       });
 
@@ -573,6 +618,21 @@ export default function Home() {
 
     // Wait for all configs to complete
     await Promise.all(configPromises);
+    if (runCancellationRef.current === cancellation) {
+      runCancellationRef.current = null;
+    }
+
+    // Save each run as completed, failed or, with results missing, cancelled
+    await Promise.all(Array.from(runs.values()).map(run =>
+      persistEvaluationRun(finishEvaluationRun(run, run.modelConfigIds.length * dataSetItems.length))
+    ));
+  };
+
+  const handleCancelRun = async () => {
+    const cancellation = runCancellationRef.current;
+    if (!cancellation) return;
+    runCancellationRef.current = null;
+    await cancellation.cancel(requestId => apiClient.cancelGeneration(requestId));
   };
 
   const handleVersionChange = async (configId: string, versionId: string) => {
@@ -1073,6 +1133,7 @@ export default function Home() {
                 selectedDataSet={selectedDataSetId ? getDataSetById(selectedDataSetId) : null}
                 onNewDataSet={handleNewDataSet}
                 onNewModelConfig={handleNewModelConfig}
+                onCancelRun={isTauriEnvironment() ? handleCancelRun : undefined}
               />
             )
           }
@@ -43,6 +43,7 @@ interface ResponsePanelProps {
   selectedDataSet?: DataSet | null;
   onNewDataSet?: (projectId: string) => void;
   onNewModelConfig?: (projectId: string) => void;
+  onCancelRun?: () => void;
 }
 
 type LayoutType = 'grid' | 'columns' | 'stacked' | 'table';
@@ -57,7 +58,7 @@ const providerIconMap: Record<string, string> = {
   lmstudio: 'lmstudio',
 };
 
-export default function ResponsePanel({ output, isGenerating = false, projectId, highlightedConfigId, showNewConfigEditor, onNewConfigClose, configResponses = {}, generatingConfigs = {}, currentPrompt, onVersionChange, onConfigSave, onDataSetChange, selectedDataSetId: propSelectedDataSetId, selectedDataSet, onNewDataSet, onNewModelConfig }: ResponsePanelProps) {
+export default function ResponsePanel({ output, isGenerating = false, projectId, highlightedConfigId, showNewConfigEditor, onNewConfigClose, configResponses = {}, generatingConfigs = {}, currentPrompt, onVersionChange, onConfigSave, onDataSetChange, selectedDataSetId: propSelectedDataSetId, selectedDataSet, onNewDataSet, onNewModelConfig, onCancelRun }: ResponsePanelProps) {
   const [apiKeys, setApiKeys] = useState<ApiKeys>({});
   const [layout, setLayout] = useState<LayoutType>('grid');
   const [modelConfigs, setModelConfigs] = useState<ProjectModelConfig[]>([]);
@@ -187,6 +188,17 @@ export default function ResponsePanel({ output, isGenerating = false, projectId,
               disabled={!configResponses || Object.keys(configResponses).length === 0}
             />
 
+            {/* Cancel Run Button: keeps the finished results, saves the run as cancelled */}
+            {onCancelRun && Object.values(generatingConfigs).some(Boolean) && (
+              <button
+                onClick={onCancelRun}
+                className="flex items-center gap-2 px-3 py-1.5 text-sm font-medium text-white bg-red-600 hover:bg-red-700 dark:bg-red-500 dark:hover:bg-red-600 rounded-md transition-colors"
+              >
+                <XMarkIcon className="h-4 w-4" />
+                <span>Cancel</span>
+              </button>
+            )}
+
             {/* Add Model Config Button */}
             {projectId && onNewModelConfig && (
               <button
@@ -0,0 +1,85 @@
+import {
+  createEvaluationRun,
+  finishEvaluationRun,
+  settledRunStatus,
+  RunCancellation,
+} from '../evaluation-runs';
+import { EvaluationResult } from '../types';
+
+// ============================================================================
+// Test Data Factories
+// ============================================================================
+
+function createMockResult(overrides: Partial<EvaluationResult> = {}): EvaluationResult {
+  return {
+    id: 'result-1',
+    modelConfigId: 'config-1',
+    output: { content: 'AI response here', tokens: 150, latency: 1234 },
+    ...overrides,
+  };
+}
+
+function createRun() {
+  return createEvaluationRun({
+    projectId: 'project-1',
+    promptId: 'prompt-1',
+    promptVersionId: 'version-1',
+    modelConfigIds: ['config-1', 'config-2'],
+  });
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+describe('createEvaluationRun', () => {
+  it('starts a running run with no results', () => {
+    const run = createRun();
+    expect(run.status).toBe('running');
+    expect(run.results).toEqual([]);
+    expect(run.id).toBeTruthy();
+    expect(run.completedAt).toBeUndefined();
+  });
+});
+
+describe('settledRunStatus', () => {
+  it('is completed when every cell has a result and one succeeded', () => {
+    const results = [createMockResult(), createMockResult({ id: 'result-2', error: 'rate limited' })];
+    expect(settledRunStatus(2, results)).toBe('completed');
+  });
+
+  it('is failed when every result is an error', () => {
+    expect(settledRunStatus(1, [createMockResult({ error: 'bad key' })])).toBe('failed');
+  });
+
+  it('is cancelled when cells are missing a result', () => {
+    expect(settledRunStatus(2, [createMockResult()])).toBe('cancelled');
+    expect(settledRunStatus(2, [])).toBe('cancelled');
+  });
+});
+
+describe('finishEvaluationRun', () => {
+  it('keeps the partial results of a cancelled run', () => {
+    const run = { ...createRun(), results: [createMockResult()] };
+    const finished = finishEvaluationRun(run, 2);
+    expect(finished.status).toBe('cancelled');
+    expect(finished.results).toHaveLength(1);
+    expect(finished.completedAt).toBeDefined();
+  });
+});
+
+describe('RunCancellation', () => {
+  it('cancels only the calls still in flight', async () => {
+    const cancellation = new RunCancellation();
+    const done = cancellation.start();
+    const pending = cancellation.start();
+    cancellation.finish(done);
+
+    const cancelGeneration = jest.fn().mockResolvedValue(true);
+    await cancellation.cancel(cancelGeneration);
+
+    expect(cancellation.cancelled).toBe(true);
+    expect(cancelGeneration).toHaveBeenCalledTimes(1);
+    expect(cancelGeneration).toHaveBeenCalledWith(pending);
+  });
+});
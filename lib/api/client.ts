@@ -44,6 +44,22 @@ class ApiClient {
     }
   }
 
+  /**
+   * Cancel a generateText call made with a requestId
+   *
+   * Only desktop calls can be cancelled; the web proxy has nothing to stop.
+   *
+   * @param requestId - The requestId the call was made with
+   * @returns Whether the call was still in flight
+   */
+  async cancelGeneration(requestId: string): Promise<boolean> {
+    if (this.environment === 'tauri') {
+      const directApi = await import('./direct');
+      return directApi.cancelGeneration(requestId);
+    }
+    return false;
+  }
+
   /**
    * Generate image from AI model
    *
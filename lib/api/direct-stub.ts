@@ -25,3 +25,7 @@ export async function generateImage(
 ): Promise<GenerateImageResponse | ApiErrorResponse> {
   return { error: 'Direct API calls are not supported in this environment' };
 }
+
+export async function cancelGeneration(requestId: string): Promise<boolean> {
+  return false;
+}
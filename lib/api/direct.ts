@@ -22,6 +22,10 @@ import {
 export async function generateText(
   request: GenerateTextRequest
 ): Promise<GenerateTextResponse | ApiErrorResponse> {
+  if (request.requestId) {
+    return generateTextCancellable(request, request.requestId);
+  }
+
   try {
     // Transform model slug if needed
     const transformedModel = transformModelSlug(request.provider, request.model);
@@ -86,6 +90,59 @@ export async function generateText(
   }
 }
 
+/**
+ * Response of the Rust `generate_text` command (camelCase ProviderResponse)
+ */
+interface RustProviderResponse {
+  content: string;
+  tokens?: number | null;
+  latency: number;
+  error?: string | null;
+}
+
+/**
+ * Generate text through the Rust providers (the `evvl run` code path), so the
+ * call can be stopped with cancelGeneration. A cancelled call resolves at once
+ * with an error; the request itself is not aborted at the provider.
+ */
+async function generateTextCancellable(
+  request: GenerateTextRequest,
+  requestId: string
+): Promise<GenerateTextResponse | ApiErrorResponse> {
+  try {
+    const { invoke } = await import('@tauri-apps/api/core');
+    const response = await invoke<RustProviderResponse>('generate_text', {
+      provider: request.provider,
+      request: {
+        model: transformModelSlug(request.provider, request.model),
+        prompt: request.prompt,
+      },
+      requestId,
+      apiKey: request.apiKey,
+    });
+    if (response.error) {
+      return { error: response.error };
+    }
+    return {
+      content: response.content,
+      tokens: response.tokens ?? 0,
+      latency: response.latency,
+    };
+  } catch (error: any) {
+    return toApiError(error, 'Failed to generate text');
+  }
+}
+
+/**
+ * Cancel a generateText call made with a requestId
+ *
+ * @returns Whether the call was still in flight
+ */
+export async function cancelGeneration(requestId: string): Promise<boolean> {
+  const { invoke } = await import('@tauri-apps/api/core');
+  return invoke<boolean>('cancel_generation', { requestId });
+}
+
 /**
  * Generate image via direct provider API call
  */
@@ -21,7 +21,8 @@ export interface BaseRequest {
  * Request for text generation
  */
 export interface GenerateTextRequest extends BaseRequest {
-  // No additional fields for text generation
+  // Desktop only: lets the call be cancelled with apiClient.cancelGeneration
+  requestId?: string;
 }
 
 /**
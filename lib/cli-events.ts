@@ -7,6 +7,7 @@
  */
 
 import { isTauriEnvironment } from './environment';
+import type { PromptMessage, ToolDefinition } from './types';
 
 // ============================================================================
 // Types
@@ -16,6 +17,9 @@ export interface CliRunConfig {
   source: 'cli';
   prompt: string;
   systemPrompt?: string;
+  messages?: PromptMessage[];
+  tools?: ToolDefinition[];
+  responseSchema?: Record<string, unknown>;
   models: string[];
   dataset?: string;
   promptId?: string;
@@ -0,0 +1,96 @@
+/**
+ * Evaluation Runs from the GUI
+ *
+ * Records a refresh of a prompt across its model configs as an evaluation
+ * run, stored in `evvl_evaluation_runs` next to the runs of `evvl run`. A run
+ * is saved as `running` before its first call and saved again once every cell
+ * has settled; a cancelled run keeps the results that finished before the
+ * cancel, like the CLI does on Ctrl-C.
+ */
+
+import { v4 as uuidv4 } from 'uuid';
+import { EvaluationResult, EvaluationRun } from './types';
+import { isTauriEnvironment } from './environment';
+import { saveEvaluationRun } from './storage';
+
+export type NewEvaluationRun = Pick<
+  EvaluationRun,
+  'projectId' | 'promptId' | 'promptVersionId' | 'modelConfigIds' | 'dataSetId'
+>;
+
+// ============================================================================
+// Run Lifecycle
+// ============================================================================
+
+export function createEvaluationRun(fields: NewEvaluationRun): EvaluationRun {
+  return {
+    id: uuidv4(),
+    ...fields,
+    results: [],
+    status: 'running',
+    createdAt: Date.now(),
+  };
+}
+
+/**
+ * Status of a run over `cells` (model config × data set item) once its calls
+ * have settled. Same rule as `run_status` in src-tauri/src/runner.rs.
+ */
+export function settledRunStatus(cells: number, results: EvaluationResult[]): EvaluationRun['status'] {
+  if (results.length < cells) {
+    return 'cancelled';
+  }
+  return results.some(result => !result.error) ? 'completed' : 'failed';
+}
+
+export function finishEvaluationRun(run: EvaluationRun, cells: number): EvaluationRun {
+  return {
+    ...run,
+    status: settledRunStatus(cells, run.results),
+    completedAt: Date.now(),
+  };
+}
+
+/**
+ * Save a run where the CLI can see it: the Tauri store on desktop,
+ * localStorage on the web
+ */
+export async function persistEvaluationRun(run: EvaluationRun): Promise<void> {
+  if (isTauriEnvironment()) {
+    const { saveEvaluationRunTauri } = await import('./storage-tauri');
+    await saveEvaluationRunTauri(run);
+  } else {
+    saveEvaluationRun(run);
+  }
+}
+
+// ============================================================================
+// Cancellation
+// ============================================================================
+
+/**
+ * The request IDs of a run's calls in flight, so a cancel can stop them all
+ */
+export class RunCancellation {
+  private inFlight = new Set<string>();
+  cancelled = false;
+
+  /** Register a call; returns the requestId to make it with */
+  start(): string {
+    const requestId = uuidv4();
+    this.inFlight.add(requestId);
+    return requestId;
+  }
+
+  finish(requestId: string): void {
+    this.inFlight.delete(requestId);
+  }
+
+  /** Mark the run cancelled and stop every call still in flight */
+  async cancel(cancelGeneration: (requestId: string) => Promise<boolean>): Promise<void> {
+    this.cancelled = true;
+    const requestIds = Array.from(this.inFlight);
+    this.inFlight.clear();
+    await Promise.all(requestIds.map(requestId => cancelGeneration(requestId)));
+  }
+}
@@ -6,7 +6,7 @@ export interface ApiKeys {
 }
 
 export interface ModelConfig {
-  provider: 'openai' | 'anthropic' | 'openrouter' | 'gemini' | 'ollama' | 'lmstudio';
+  provider: 'openai' | 'anthropic' | 'openrouter' | 'gemini' | 'ollama' | 'lmstudio' | 'mock';
   model: string;
   label: string;
 }
@@ -22,6 +22,21 @@ export interface AIOutput {
   tokens?: number;
   latency?: number;
   timestamp: number;
+
+  // Recorded by the CLI runner
+  errorDetails?: { kind: string; status?: number; reason?: string; categories?: string[] };
+  inputTokens?: number;
+  outputTokens?: number;
+  cachedTokens?: number;     // Part of inputTokens served from the prompt cache
+  cacheWriteTokens?: number; // Part of inputTokens written to the prompt cache
+  reasoningTokens?: number;  // Part of outputTokens spent on hidden reasoning
+  finishReason?: string;     // Provider's stop reason (e.g. 'length', 'max_tokens')
+  cost?: number;             // USD, billed or estimated from token prices
+  upstreamProvider?: string; // OpenRouter: provider that served the request
+  toolCalls?: ToolCall[];    // Functions the model called
+  attempts?: number;         // Calls made, retries included
+  cached?: boolean;          // Served from the CLI response cache
+  parameters?: AIParameters; // Merged parameters that were sent
 }
 
 export interface Rating {
@@ -99,11 +114,34 @@ export interface PromptVersion {
   versionNumber: number;
   content: string;              // The prompt template (may include {{variables}})
   systemPrompt?: string;
+  messages?: PromptMessage[];   // Multi-turn conversation, sent instead of content
+  tools?: ToolDefinition[];     // Functions the model may call
+  responseSchema?: Record<string, unknown>;  // JSON Schema every output is checked against
   parameters?: AIParameters;    // Default parameters for this version
   note?: string;                // What changed in this version
   createdAt: number;
 }
 
+// One turn of a conversation prompt (may include {{variables}})
+export interface PromptMessage {
+  role: 'system' | 'user' | 'assistant';
+  content: string;
+}
+
+// A function offered to the model; parameters is a JSON Schema object
+export interface ToolDefinition {
+  name: string;
+  description?: string;
+  parameters?: Record<string, unknown>;
+}
+
+// A function call made by the model
+export interface ToolCall {
+  id?: string;
+  name: string;
+  arguments: unknown;           // Parsed JSON; a string when the model sent invalid JSON
+}
+
 // Model configs are reusable model + parameter combinations
 export interface ProjectModelConfig {
   id: string;
@@ -112,6 +150,8 @@ export interface ProjectModelConfig {
   provider: Provider;
   model: string;                // e.g., "gpt-4"
   parameters?: AIParameters;    // Custom parameters for this config
+  maxAttempts?: number;         // CLI runner: attempts per call, retries included (default 3)
+  timeoutMs?: number;           // CLI runner: timeout per attempt (default 300000)
   createdAt: number;
 }
 
@@ -129,6 +169,13 @@ export interface DataSetItem {
   id: string;
   name?: string;
   variables: Record<string, string>;  // {"text": "Article...", "tone": "professional"}
+  expectedToolCalls?: ExpectedToolCall[];  // [] expects no tool call
+}
+
+// arguments match as a subset: listed keys must be equal, others are ignored
+export interface ExpectedToolCall {
+  name: string;
+  arguments?: Record<string, unknown>;
 }
 
 // Evaluation runs track executions of prompts
@@ -140,7 +187,7 @@ export interface EvaluationRun {
   modelConfigIds: string[];      // Which models to test
   dataSetId?: string;            // Optional batch testing
   results: EvaluationResult[];
-  status: 'running' | 'completed' | 'failed';
+  status: 'running' | 'completed' | 'failed' | 'cancelled';
   createdAt: number;
   completedAt?: number;
 }
@@ -152,4 +199,12 @@ export interface EvaluationResult {
   output: AIOutput;
   rating?: Rating;
   error?: string;
+  assertions?: AssertionResult[];  // Checks against the data set item's expectations
+  schemaErrors?: string[];         // Violations of the version's responseSchema; [] when the output conforms
+}
+
+export interface AssertionResult {
+  kind: 'tool_name' | 'tool_arguments' | 'no_tool_call';
+  passed: boolean;
+  message: string;
 }
@@ -0,0 +1,144 @@
+//! On-disk cache of successful provider responses, so re-running an
+//! evaluation only bills the cells whose request actually changed.
+//!
+//! Entries live in `~/.evvl/cache/responses/<key>.json`. The key is a
+//! SHA-256 over the provider and the fully rendered request: model, merged
+//! parameters, system prompt and prompt.
+
+use crate::providers::{ProviderRequest, ProviderResponse};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+
+/// Bumped whenever the key inputs or the entry format change
+const CACHE_VERSION: u32 = 1;
+
+pub struct ResponseCache {
+    dir: PathBuf,
+    /// `false` for `--refresh`: always call the provider, then overwrite the entry
+    read: bool,
+}
+
+impl ResponseCache {
+    pub fn new(dir: PathBuf, read: bool) -> Self {
+        Self { dir, read }
+    }
+
+    /// The cache under `~/.evvl`
+    pub fn open(refresh: bool) -> Self {
+        let mut dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+        dir.push(".evvl");
+        dir.push("cache");
+        dir.push("responses");
+        Self::new(dir, !refresh)
+    }
+
+    /// Fingerprint of everything that determines the response
+    pub fn key(provider: &str, request: &ProviderRequest) -> String {
+        let fingerprint = json!({
+            "version": CACHE_VERSION,
+            "provider": provider,
+            "request": request,
+        });
+        Sha256::digest(fingerprint.to_string().as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    pub fn get(&self, key: &str) -> Option<ProviderResponse> {
+        if !self.read {
+            return None;
+        }
+        let content = std::fs::read_to_string(self.path(key)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// Store a successful response. Failures are never cached, and a cache
+    /// that can't be written only costs the next run a provider call.
+    pub fn put(&self, key: &str, response: &ProviderResponse) {
+        if response.error.is_some() {
+            return;
+        }
+        if std::fs::create_dir_all(&self.dir).is_err() {
+            return;
+        }
+        // Write then rename, so a concurrent reader never sees half an entry
+        let temp = self.dir.join(format!("{}.{}.tmp", key, uuid::Uuid::new_v4()));
+        let written = serde_json::to_string(response)
+            .map_err(|e| e.to_string())
+            .and_then(|content| std::fs::write(&temp, content).map_err(|e| e.to_string()))
+            .and_then(|_| std::fs::rename(&temp, self.path(key)).map_err(|e| e.to_string()));
+        if written.is_err() {
+            let _ = std::fs::remove_file(&temp);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parameters::AIParameters;
+
+    fn request(prompt: &str, temperature: f64) -> ProviderRequest {
+        ProviderRequest {
+            model: "gpt-4o".to_string(),
+            prompt: prompt.to_string(),
+            system_prompt: Some("Be brief".to_string()),
+            parameters: Some(AIParameters { temperature: Some(temperature), ..Default::default() }),
+            ..Default::default()
+        }
+    }
+
+    fn response(error: Option<&str>) -> ProviderResponse {
+        serde_json::from_value(json!({
+            "content": "Hi",
+            "tokens": 3,
+            "inputTokens": 2,
+            "outputTokens": 1,
+            "cachedTokens": null,
+            "reasoningTokens": null,
+            "finishReason": "stop",
+            "cost": null,
+            "upstreamProvider": null,
+            "latency": 120,
+            "error": error,
+            "errorDetails": null
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_key_covers_request_fields() {
+        let key = ResponseCache::key("openai", &request("Hello", 0.2));
+        assert_eq!(key.len(), 64);
+        assert_eq!(key, ResponseCache::key("openai", &request("Hello", 0.2)));
+        assert_ne!(key, ResponseCache::key("openrouter", &request("Hello", 0.2)));
+        assert_ne!(key, ResponseCache::key("openai", &request("Hello!", 0.2)));
+        assert_ne!(key, ResponseCache::key("openai", &request("Hello", 0.3)));
+    }
+
+    #[test]
+    fn test_round_trip_and_refresh() {
+        let dir = std::env::temp_dir().join(format!("evvl-cache-{}", uuid::Uuid::new_v4()));
+        let cache = ResponseCache::new(dir.clone(), true);
+
+        cache.put("failed", &response(Some("boom")));
+        assert!(cache.get("failed").is_none());
+
+        cache.put("ok", &response(None));
+        let hit = cache.get("ok").unwrap();
+        assert_eq!(hit.content, "Hi");
+        assert_eq!(hit.input_tokens, Some(2));
+
+        // Refresh never reads existing entries
+        let refresh = ResponseCache::new(dir.clone(), false);
+        assert!(refresh.get("ok").is_none());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
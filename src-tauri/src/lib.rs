@@ -12,6 +12,16 @@ use tauri::{
 use tauri_plugin_cli::CliExt;
 use tauri_plugin_global_shortcut::GlobalShortcutExt;
 
+mod cache;
+mod parameters;
+mod pricing;
+mod progress;
+mod providers;
+mod runner;
+mod schema;
+mod template;
+mod tools;
+
 // ============================================================================
 // Data Types (matching TypeScript types)
 // ============================================================================
@@ -41,12 +51,38 @@ pub struct PromptVersion {
     pub content: String,
     #[serde(rename = "systemPrompt")]
     pub system_prompt: Option<String>,
-    pub parameters: Option<Value>,
+    /// Conversation turns of a multi-turn version, sent instead of `content`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub messages: Vec<PromptMessage>,
+    /// Functions offered to the model on every call of this version
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<tools::ToolDefinition>,
+    /// JSON Schema the output must follow; requested natively where the
+    /// provider supports it and checked on every output
+    #[serde(rename = "responseSchema", default, skip_serializing_if = "Option::is_none")]
+    pub response_schema: Option<Value>,
+    pub parameters: Option<parameters::AIParameters>,
     pub note: Option<String>,
     #[serde(rename = "createdAt")]
     pub created_at: i64,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageRole {
+    System,
+    User,
+    Assistant,
+}
+
+/// One turn of a conversation prompt; `content` is a template like the
+/// version's `content`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PromptMessage {
+    pub role: MessageRole,
+    pub content: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Prompt {
     pub id: String,
@@ -71,7 +107,13 @@ pub struct ProjectModelConfig {
     pub name: String,
     pub provider: String,
     pub model: String,
-    pub parameters: Option<Value>,
+    pub parameters: Option<parameters::AIParameters>,
+    /// Attempts per call for the CLI runner, retries included (default 3)
+    #[serde(rename = "maxAttempts", default, skip_serializing_if = "Option::is_none")]
+    pub max_attempts: Option<u32>,
+    /// Per-request timeout for the CLI runner in milliseconds (default 300s)
+    #[serde(rename = "timeoutMs", default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
     #[serde(rename = "createdAt")]
     pub created_at: i64,
 }
@@ -81,6 +123,10 @@ pub struct DataSetItem {
     pub id: String,
     pub name: Option<String>,
     pub variables: HashMap<String, String>,
+    /// Tool calls the model should make for this item; an empty list
+    /// expects no call at all
+    #[serde(rename = "expectedToolCalls", default, skip_serializing_if = "Option::is_none")]
+    pub expected_tool_calls: Option<Vec<tools::ExpectedToolCall>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -109,7 +155,7 @@ pub struct EvaluationRun {
     pub model_config_ids: Vec<String>,
     #[serde(rename = "dataSetId")]
     pub data_set_id: Option<String>,
-    pub results: Vec<Value>,
+    pub results: Vec<EvaluationResult>,
     pub status: String,
     #[serde(rename = "createdAt")]
     pub created_at: i64,
@@ -117,7 +163,133 @@ pub struct EvaluationRun {
     pub completed_at: Option<i64>,
 }
 
+impl EvaluationRun {
+    /// Parse a stored run record by record, so a malformed result is
+    /// reported with its position instead of failing (or defaulting) silently
+    fn from_stored(value: &Value) -> Result<EvaluationRun, String> {
+        let mut header = value.clone();
+        let raw_results = match header.get_mut("results").map(Value::take) {
+            Some(Value::Array(results)) => results,
+            Some(_) => return Err("`results` is not an array".to_string()),
+            None => return Err("missing field `results`".to_string()),
+        };
+        header["results"] = json!([]);
+        let mut run: EvaluationRun = serde_json::from_value(header).map_err(|e| e.to_string())?;
+
+        let mut problems = Vec::new();
+        for (index, raw) in raw_results.into_iter().enumerate() {
+            let id = raw.get("id").and_then(|v| v.as_str()).unwrap_or("?").to_string();
+            match serde_json::from_value::<EvaluationResult>(raw) {
+                Ok(result) => run.results.push(result),
+                Err(e) => problems.push(format!("result #{} (id {}): {}", index + 1, id, e)),
+            }
+        }
+        if problems.is_empty() {
+            Ok(run)
+        } else {
+            Err(problems.join("\n"))
+        }
+    }
+}
+
+/// Which model produced an output, as shown in the GUI
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelConfig {
+    pub provider: String,
+    pub model: String,
+    pub label: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AIOutput {
+    pub id: String,
+    #[serde(rename = "modelConfig")]
+    pub model_config: ModelConfig,
+    #[serde(rename = "type")]
+    pub output_type: String,
+    pub content: String,
+    #[serde(rename = "imageUrl", skip_serializing_if = "Option::is_none")]
+    pub image_url: Option<String>,
+    #[serde(rename = "imageData", skip_serializing_if = "Option::is_none")]
+    pub image_data: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(rename = "errorDetails", skip_serializing_if = "Option::is_none")]
+    pub error_details: Option<providers::ErrorDetails>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<i64>,
+    #[serde(rename = "inputTokens", skip_serializing_if = "Option::is_none")]
+    pub input_tokens: Option<i64>,
+    #[serde(rename = "outputTokens", skip_serializing_if = "Option::is_none")]
+    pub output_tokens: Option<i64>,
+    /// Part of `inputTokens` served from the provider's prompt cache
+    #[serde(rename = "cachedTokens", skip_serializing_if = "Option::is_none")]
+    pub cached_tokens: Option<i64>,
+    /// Part of `inputTokens` written to the provider's prompt cache
+    #[serde(rename = "cacheWriteTokens", skip_serializing_if = "Option::is_none")]
+    pub cache_write_tokens: Option<i64>,
+    /// Part of `outputTokens` spent on hidden reasoning
+    #[serde(rename = "reasoningTokens", skip_serializing_if = "Option::is_none")]
+    pub reasoning_tokens: Option<i64>,
+    /// Provider's stop reason, e.g. `length` / `max_tokens` when truncated
+    #[serde(rename = "finishReason", skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency: Option<i64>,
+    /// Cost in USD: billed by the provider, or estimated by the CLI runner
+    /// from the pricing registry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost: Option<f64>,
+    #[serde(rename = "upstreamProvider", skip_serializing_if = "Option::is_none")]
+    pub upstream_provider: Option<String>,
+    /// Tool calls the model made instead of (or besides) answering in text
+    #[serde(rename = "toolCalls", skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<tools::ToolCall>>,
+    /// Calls made by the CLI runner, retries included
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attempts: Option<u32>,
+    /// Served from the CLI response cache
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cached: Option<bool>,
+    /// Merged parameters the CLI runner sent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<parameters::AIParameters>,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rating {
+    #[serde(rename = "outputId")]
+    pub output_id: String,
+    /// 1-5
+    pub score: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvaluationResult {
+    pub id: String,
+    #[serde(rename = "modelConfigId")]
+    pub model_config_id: String,
+    #[serde(rename = "dataSetItemId", skip_serializing_if = "Option::is_none")]
+    pub data_set_item_id: Option<String>,
+    pub output: AIOutput,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rating: Option<Rating>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Checks against the dataset item's expectations, e.g. its expected
+    /// tool calls
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub assertions: Vec<tools::AssertionResult>,
+    /// Where the output breaks the version's JSON Schema; empty when it
+    /// conforms, absent when there was no schema or no output
+    #[serde(rename = "schemaErrors", skip_serializing_if = "Option::is_none")]
+    pub schema_errors: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ApiKeys {
     pub openai: Option<String>,
     pub anthropic: Option<String>,
@@ -155,6 +327,28 @@ struct CliRunOutput {
     prompt: String,
     results: Vec<CliRunResult>,
     status: String,
+    /// Sum of the per-result costs that could be determined
+    #[serde(rename = "totalCost")]
+    total_cost: Option<f64>,
+    /// Successful results with no known cost, left out of `totalCost`
+    /// (which is then only a lower bound)
+    #[serde(rename = "unpricedResults")]
+    unpriced_results: usize,
+    /// Per model, results whose tool calls passed every assertion
+    #[serde(rename = "toolPassRates", skip_serializing_if = "Vec::is_empty")]
+    tool_pass_rates: Vec<CliPassRate>,
+    /// Per model, outputs that conform to the response schema
+    #[serde(rename = "schemaConformance", skip_serializing_if = "Vec::is_empty")]
+    schema_conformance: Vec<CliPassRate>,
+}
+
+/// How many of a model's graded results passed
+#[derive(Debug, PartialEq, Serialize)]
+struct CliPassRate {
+    model: String,
+    passed: usize,
+    graded: usize,
+    rate: f64,
 }
 
 #[derive(Debug, Serialize)]
@@ -162,9 +356,29 @@ struct CliRunResult {
     model: String,
     provider: String,
     content: Option<String>,
-    tokens: Option<i32>,
+    tokens: Option<i64>,
+    #[serde(rename = "inputTokens")]
+    input_tokens: Option<i64>,
+    #[serde(rename = "outputTokens")]
+    output_tokens: Option<i64>,
+    #[serde(rename = "cachedTokens")]
+    cached_tokens: Option<i64>,
+    #[serde(rename = "reasoningTokens")]
+    reasoning_tokens: Option<i64>,
+    #[serde(rename = "finishReason")]
+    finish_reason: Option<String>,
     latency: Option<i64>,
+    /// USD, billed by the provider or estimated from the pricing registry
+    cost: Option<f64>,
+    /// Served from the response cache
+    cached: bool,
     error: Option<String>,
+    #[serde(rename = "toolCalls", skip_serializing_if = "Option::is_none")]
+    tool_calls: Option<Vec<tools::ToolCall>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    assertions: Vec<tools::AssertionResult>,
+    #[serde(rename = "schemaErrors", skip_serializing_if = "Option::is_none")]
+    schema_errors: Option<Vec<String>>,
 }
 
 // ============================================================================
@@ -217,6 +431,75 @@ fn save_to_store<T: Serialize>(key: &str, value: &T) -> Result<(), String> {
     Ok(())
 }
 
+/// The list stored under `key`, each record read on its own: one that
+/// doesn't parse (hand-edited, or written by a newer app) is skipped with a
+/// warning instead of hiding the whole list
+fn load_records<T: for<'de> Deserialize<'de>>(key: &str) -> Vec<T> {
+    let (records, problems) = parse_records(load_from_store(key).unwrap_or_default());
+    for problem in problems {
+        warn_once(format!("Warning: skipping unreadable {} record {}", key, problem));
+    }
+    records
+}
+
+/// Save a list read with `load_records`, keeping the records it skipped
+fn save_records<T: Serialize + for<'de> Deserialize<'de>>(key: &str, records: &[T]) -> Result<(), String> {
+    let stored: Vec<Value> = load_from_store(key).unwrap_or_default();
+    save_to_store(key, &with_unreadable(records, stored)?)
+}
+
+/// `records` followed by the `stored` records that don't parse
+fn with_unreadable<T: Serialize + for<'de> Deserialize<'de>>(
+    records: &[T],
+    stored: Vec<Value>,
+) -> Result<Vec<Value>, String> {
+    let mut all = records
+        .iter()
+        .map(|record| serde_json::to_value(record).map_err(|e| e.to_string()))
+        .collect::<Result<Vec<Value>, String>>()?;
+    all.extend(stored.into_iter().filter(|raw| serde_json::from_value::<T>(raw.clone()).is_err()));
+    Ok(all)
+}
+
+/// Records that parse, and one problem for each that doesn't
+fn parse_records<T: for<'de> Deserialize<'de>>(raw: Vec<Value>) -> (Vec<T>, Vec<String>) {
+    let mut records = Vec::new();
+    let mut problems = Vec::new();
+    for (index, raw) in raw.into_iter().enumerate() {
+        let id = raw.get("id").and_then(|v| v.as_str()).unwrap_or("?").to_string();
+        match serde_json::from_value(raw) {
+            Ok(record) => records.push(record),
+            Err(e) => problems.push(format!("#{} (id {}): {}", index + 1, id, e)),
+        }
+    }
+    (records, problems)
+}
+
+/// Print a warning once per process; the store is read several times per command
+fn warn_once(message: String) {
+    static SHOWN: std::sync::OnceLock<std::sync::Mutex<std::collections::HashSet<String>>> =
+        std::sync::OnceLock::new();
+    if SHOWN.get_or_init(Default::default).lock().unwrap().insert(message.clone()) {
+        eprintln!("{}", message);
+    }
+}
+
+/// Resolve API keys for headless runs: environment variables take precedence
+/// over keys saved in the app
+fn resolve_api_keys() -> ApiKeys {
+    let env = get_env_api_keys();
+    let stored: Option<ApiKeys> = load_from_store("evvl_api_keys");
+    match stored {
+        Some(stored) => ApiKeys {
+            openai: env.openai.or(stored.openai),
+            anthropic: env.anthropic.or(stored.anthropic),
+            openrouter: env.openrouter.or(stored.openrouter),
+            gemini: env.gemini.or(stored.gemini),
+        },
+        None => env,
+    }
+}
+
 // ============================================================================
 // Git Detection
 // ============================================================================
@@ -245,7 +528,7 @@ fn detect_git_repo() -> Option<String> {
 
 /// Get or create a project for the current git repo
 fn get_or_create_repo_project(repo_name: &str, json_output: bool) -> Option<Project> {
-    let mut projects: Vec<Project> = load_from_store("evvl_projects_v2").unwrap_or_default();
+    let mut projects: Vec<Project> = load_records("evvl_projects_v2");
 
     // Check if project already exists
     if let Some(existing) = projects.iter().find(|p| p.name.to_lowercase() == repo_name.to_lowercase()) {
@@ -266,7 +549,7 @@ fn get_or_create_repo_project(repo_name: &str, json_output: bool) -> Option<Proj
     };
 
     projects.push(project.clone());
-    if let Err(e) = save_to_store("evvl_projects_v2", &projects) {
+    if let Err(e) = save_records("evvl_projects_v2", &projects) {
         eprintln!("Warning: Failed to save project: {}", e);
         return None;
     }
@@ -280,7 +563,7 @@ fn get_or_create_repo_project(repo_name: &str, json_output: bool) -> Option<Proj
 
 /// Get or create a default prompt for a project (named after the project)
 fn get_or_create_default_prompt(project: &mut Project, json_output: bool) -> Option<Prompt> {
-    let mut prompts: Vec<Prompt> = load_from_store("evvl_prompts_v2").unwrap_or_default();
+    let mut prompts: Vec<Prompt> = load_records("evvl_prompts_v2");
 
     // Check if project has any prompts
     if let Some(existing) = prompts.iter().find(|p| p.project_id == project.id) {
@@ -302,6 +585,9 @@ fn get_or_create_default_prompt(project: &mut Project, json_output: bool) -> Opt
             version_number: 1,
             content: String::new(), // Will be filled in by the run command
             system_prompt: None,
+            messages: Vec::new(),
+            tools: Vec::new(),
+            response_schema: None,
             parameters: None,
             note: Some("Initial version".to_string()),
             created_at: now,
@@ -316,18 +602,18 @@ fn get_or_create_default_prompt(project: &mut Project, json_output: bool) -> Opt
 
     // Save both
     prompts.push(prompt.clone());
-    if let Err(e) = save_to_store("evvl_prompts_v2", &prompts) {
+    if let Err(e) = save_records("evvl_prompts_v2", &prompts) {
         eprintln!("Warning: Failed to save prompt: {}", e);
         return None;
     }
 
     // Update project in store
-    let mut projects: Vec<Project> = load_from_store("evvl_projects_v2").unwrap_or_default();
+    let mut projects: Vec<Project> = load_records("evvl_projects_v2");
     if let Some(p) = projects.iter_mut().find(|p| p.id == project.id) {
         p.prompt_ids = project.prompt_ids.clone();
         p.updated_at = now;
     }
-    let _ = save_to_store("evvl_projects_v2", &projects);
+    let _ = save_records("evvl_projects_v2", &projects);
 
     if !json_output {
         println!("Created prompt '{}' for project", project.name);
@@ -336,12 +622,77 @@ fn get_or_create_default_prompt(project: &mut Project, json_output: bool) -> Opt
     Some(prompt)
 }
 
+/// Resolve `provider/model` specs to model configs, creating configs in the
+/// project for any model it doesn't have yet. Without a project the configs
+/// are ad-hoc and not saved.
+fn get_or_create_model_configs(
+    project: Option<&mut Project>,
+    model_list: &[String],
+    json_output: bool,
+) -> Vec<ProjectModelConfig> {
+    let mut all_configs: Vec<ProjectModelConfig> = load_records("evvl_model_configs_v2");
+    let now = chrono::Utc::now().timestamp_millis();
+    let project_id = project.as_ref().map(|p| p.id.clone()).unwrap_or_default();
+    let mut created = Vec::new();
+
+    let configs: Vec<ProjectModelConfig> = model_list
+        .iter()
+        .map(|spec| {
+            let (provider, model) = runner::parse_model_spec(spec);
+            if let Some(existing) = all_configs.iter().find(|c| {
+                c.project_id == project_id && c.provider == provider && c.model == model
+            }) {
+                return existing.clone();
+            }
+
+            let config = ProjectModelConfig {
+                id: uuid::Uuid::new_v4().to_string(),
+                project_id: project_id.clone(),
+                name: model.clone(),
+                provider,
+                model,
+                parameters: None,
+                max_attempts: None,
+                timeout_ms: None,
+                created_at: now,
+            };
+            created.push(config.clone());
+            config
+        })
+        .collect();
+
+    if let Some(proj) = project {
+        if !created.is_empty() {
+            proj.model_config_ids.extend(created.iter().map(|c| c.id.clone()));
+            all_configs.extend(created.iter().cloned());
+            if let Err(e) = save_records("evvl_model_configs_v2", &all_configs) {
+                eprintln!("Warning: Failed to save model configs: {}", e);
+            }
+
+            let mut projects: Vec<Project> = load_records("evvl_projects_v2");
+            if let Some(p) = projects.iter_mut().find(|p| p.id == proj.id) {
+                p.model_config_ids = proj.model_config_ids.clone();
+                p.updated_at = now;
+            }
+            let _ = save_records("evvl_projects_v2", &projects);
+
+            if !json_output {
+                for config in &created {
+                    println!("Added model config '{}/{}' to project", config.provider, config.model);
+                }
+            }
+        }
+    }
+
+    configs
+}
+
 // ============================================================================
 // CLI Command Handlers
 // ============================================================================
 
 fn handle_projects_command(json_output: bool) -> i32 {
-    let projects: Vec<Project> = load_from_store("evvl_projects_v2").unwrap_or_default();
+    let projects: Vec<Project> = load_records("evvl_projects_v2");
 
     if json_output {
         let output: Vec<CliProjectOutput> = projects
@@ -380,8 +731,8 @@ fn handle_projects_command(json_output: bool) -> i32 {
 }
 
 fn handle_prompts_list_command(project_filter: Option<&str>, json_output: bool) -> i32 {
-    let prompts: Vec<Prompt> = load_from_store("evvl_prompts_v2").unwrap_or_default();
-    let projects: Vec<Project> = load_from_store("evvl_projects_v2").unwrap_or_default();
+    let prompts: Vec<Prompt> = load_records("evvl_prompts_v2");
+    let projects: Vec<Project> = load_records("evvl_projects_v2");
 
     // Filter by project if specified
     let filtered_prompts: Vec<&Prompt> = if let Some(filter) = project_filter {
@@ -451,19 +802,68 @@ fn handle_prompts_list_command(project_filter: Option<&str>, json_output: bool)
     0
 }
 
+fn handle_models_command(json_output: bool) -> i32 {
+    let provider = providers::OllamaProvider::from_env();
+    let models = match provider.list_models() {
+        Ok(models) => models,
+        Err(e) => {
+            eprintln!("Error: Ollama {}", e);
+            return 1;
+        }
+    };
+
+    if json_output {
+        println!("{}", serde_json::to_string_pretty(&models).unwrap());
+    } else if models.is_empty() {
+        println!("No Ollama models installed.");
+        println!("Pull one with 'ollama pull llama3', then run 'evvl run -m ollama/llama3'.");
+    } else {
+        println!("Ollama models ({}):", models.len());
+        println!("{:<40}  {:>8}  {:>8}  Quantization", "Name", "Size", "Params");
+        println!("{}", "-".repeat(80));
+        for model in &models {
+            let details = model.details.as_ref();
+            println!(
+                "{:<40}  {:>6.1}GB  {:>8}  {}",
+                truncate_string(&model.name, 40),
+                model.size as f64 / 1_000_000_000.0,
+                details.and_then(|d| d.parameter_size.as_deref()).unwrap_or("-"),
+                details.and_then(|d| d.quantization_level.as_deref()).unwrap_or("-")
+            );
+        }
+    }
+    0
+}
+
 fn handle_export_command(run_id: Option<&str>, format: Option<&str>, _json_output: bool) -> i32 {
-    let runs: Vec<EvaluationRun> = load_from_store("evvl_evaluation_runs").unwrap_or_default();
-    let model_configs: Vec<ProjectModelConfig> = load_from_store("evvl_model_configs_v2").unwrap_or_default();
-    let prompts: Vec<Prompt> = load_from_store("evvl_prompts_v2").unwrap_or_default();
+    // Runs are parsed one at a time so a single bad record can be reported
+    // instead of making every run unreadable
+    let runs: Vec<Value> = load_from_store("evvl_evaluation_runs").unwrap_or_default();
+    let prompts: Vec<Prompt> = load_records("evvl_prompts_v2");
+
+    let field = |run: &Value, name: &str| run.get(name).cloned().unwrap_or(Value::Null);
 
     // If no run ID specified, list recent runs
     let run = if let Some(id) = run_id {
-        runs.iter().find(|r| r.id == id)
+        runs.iter().find(|r| field(r, "id") == id)
     } else {
         // Get most recent completed run
         runs.iter()
-            .filter(|r| r.status == "completed")
-            .max_by_key(|r| r.created_at)
+            .filter(|r| field(r, "status") == "completed")
+            .max_by_key(|r| field(r, "createdAt").as_i64().unwrap_or(0))
+    };
+
+    let run = match run.map(EvaluationRun::from_stored) {
+        Some(Ok(run)) => Some(run),
+        Some(Err(problems)) => {
+            let id = run.and_then(|r| r.get("id")).and_then(|v| v.as_str()).unwrap_or("?");
+            eprintln!("Error: Run {} has malformed records:", id);
+            for problem in problems.lines() {
+                eprintln!("  {}", problem);
+            }
+            return 1;
+        }
+        None => None,
     };
 
     match run {
@@ -475,51 +875,51 @@ fn handle_export_command(run_id: Option<&str>, format: Option<&str>, _json_outpu
                 .unwrap_or_else(|| "Unknown".to_string());
 
             let export_format = format.unwrap_or("json");
+            let pricing = pricing::PricingRegistry::load();
 
             match export_format {
                 "csv" => {
-                    println!("model,provider,content,tokens,latency,error");
+                    println!(
+                        "model,provider,content,tokens,latency,error,\
+                         input_tokens,output_tokens,cached_tokens,reasoning_tokens,finish_reason,cost"
+                    );
+                    let count = |n: Option<i64>| n.map(|n| n.to_string()).unwrap_or_default();
                     for result in &r.results {
-                        let model_id = result.get("modelConfigId").and_then(|v| v.as_str()).unwrap_or("");
-                        let config = model_configs.iter().find(|c| c.id == model_id);
-                        let output = result.get("output").unwrap_or(&Value::Null);
-
-                        let model_name = config.map(|c| c.model.as_str()).unwrap_or("unknown");
-                        let provider = config.map(|c| c.provider.as_str()).unwrap_or("unknown");
-                        let content = output.get("content").and_then(|v| v.as_str()).unwrap_or("");
-                        let tokens = output.get("tokens").and_then(|v| v.as_i64()).unwrap_or(0);
-                        let latency = output.get("latency").and_then(|v| v.as_i64()).unwrap_or(0);
-                        let error = output.get("error").and_then(|v| v.as_str()).unwrap_or("");
+                        let output = &result.output;
+                        let error = result.error.as_deref().or(output.error.as_deref()).unwrap_or("");
 
                         // CSV escape content
-                        let escaped_content = content.replace("\"", "\"\"");
+                        let escaped_content = output.content.replace("\"", "\"\"");
                         println!(
-                            "\"{}\",\"{}\",\"{}\",{},{},\"{}\"",
-                            model_name, provider, escaped_content, tokens, latency, error
+                            "\"{}\",\"{}\",\"{}\",{},{},\"{}\",{},{},{},{},{},{}",
+                            output.model_config.model,
+                            output.model_config.provider,
+                            escaped_content,
+                            output.tokens.unwrap_or(0),
+                            output.latency.unwrap_or(0),
+                            error.replace("\"", "\"\""),
+                            count(output.input_tokens),
+                            count(output.output_tokens),
+                            count(output.cached_tokens),
+                            count(output.reasoning_tokens),
+                            output.finish_reason.as_deref().unwrap_or(""),
+                            pricing.output_cost(output).map(|c| c.to_string()).unwrap_or_default()
                         );
                     }
                 }
                 _ => {
                     // JSON output
-                    let results: Vec<CliRunResult> = r.results.iter().map(|result| {
-                        let model_id = result.get("modelConfigId").and_then(|v| v.as_str()).unwrap_or("");
-                        let config = model_configs.iter().find(|c| c.id == model_id);
-                        let output = result.get("output").unwrap_or(&Value::Null);
-
-                        CliRunResult {
-                            model: config.map(|c| c.model.clone()).unwrap_or_else(|| "unknown".to_string()),
-                            provider: config.map(|c| c.provider.clone()).unwrap_or_else(|| "unknown".to_string()),
-                            content: output.get("content").and_then(|v| v.as_str()).map(|s| s.to_string()),
-                            tokens: output.get("tokens").and_then(|v| v.as_i64()).map(|n| n as i32),
-                            latency: output.get("latency").and_then(|v| v.as_i64()),
-                            error: output.get("error").and_then(|v| v.as_str()).map(|s| s.to_string()),
-                        }
-                    }).collect();
+                    let results: Vec<CliRunResult> =
+                        r.results.iter().map(|result| cli_run_result(result, &pricing)).collect();
 
                     let output = CliRunOutput {
                         id: r.id.clone(),
                         timestamp: r.created_at,
                         prompt: prompt_content,
+                        total_cost: pricing::total_cost(results.iter().map(|r| r.cost)),
+                        unpriced_results: unpriced_results(&results),
+                        tool_pass_rates: tool_pass_rates(&results),
+                        schema_conformance: schema_conformance(&results),
                         results,
                         status: r.status.clone(),
                     };
@@ -539,20 +939,87 @@ fn handle_export_command(run_id: Option<&str>, format: Option<&str>, _json_outpu
     }
 }
 
-fn handle_run_command(
-    prompt_text: Option<&str>,
-    prompt_name: Option<&str>,
-    version_note: Option<&str>,
-    models: Option<&str>,
-    dataset_name: Option<&str>,
+/// What `evvl run` (or the bare `evvl "<prompt>"` form) was asked to do
+struct RunOptions<'a> {
+    prompt_text: Option<&'a str>,
+    system_prompt: Option<&'a str>,
+    messages: Option<Vec<PromptMessage>>,
+    tools: Option<Vec<tools::ToolDefinition>>,
+    response_schema: Option<Value>,
+    prompt_name: Option<&'a str>,
+    version_note: Option<&'a str>,
+    /// Comma-separated `provider/model` specs; the project's models when absent
+    models: Option<&'a str>,
+    dataset_name: Option<&'a str>,
     no_dataset: bool,
-    project_filter: Option<&str>,
+    project_filter: Option<&'a str>,
     json_output: bool,
     open_gui: bool,
-) -> i32 {
-    let projects: Vec<Project> = load_from_store("evvl_projects_v2").unwrap_or_default();
-    let model_configs: Vec<ProjectModelConfig> = load_from_store("evvl_model_configs_v2").unwrap_or_default();
-    let data_sets: Vec<DataSet> = load_from_store("evvl_data_sets_v2").unwrap_or_default();
+    stream: bool,
+    concurrency: usize,
+    strict: bool,
+    cli_parameters: parameters::AIParameters,
+    cache: Option<cache::ResponseCache>,
+    deadline: Option<std::time::Duration>,
+    cancel: providers::CancelToken,
+}
+
+impl Default for RunOptions<'_> {
+    fn default() -> Self {
+        Self {
+            prompt_text: None,
+            system_prompt: None,
+            messages: None,
+            tools: None,
+            response_schema: None,
+            prompt_name: None,
+            version_note: None,
+            models: None,
+            dataset_name: None,
+            no_dataset: false,
+            project_filter: None,
+            json_output: false,
+            open_gui: false,
+            stream: false,
+            concurrency: runner::DEFAULT_CONCURRENCY,
+            strict: false,
+            cli_parameters: parameters::AIParameters::default(),
+            cache: None,
+            deadline: None,
+            cancel: providers::CancelToken::default(),
+        }
+    }
+}
+
+/// Models `evvl run` uses when neither `--models` nor the project gives any
+const DEFAULT_MODELS: [&str; 2] = ["anthropic/claude-3-5-sonnet", "openai/gpt-4"];
+
+fn handle_run_command(options: RunOptions) -> i32 {
+    let RunOptions {
+        prompt_text,
+        system_prompt,
+        messages,
+        tools,
+        response_schema,
+        prompt_name,
+        version_note,
+        models,
+        dataset_name,
+        no_dataset,
+        project_filter,
+        json_output,
+        open_gui,
+        stream,
+        concurrency,
+        strict,
+        cli_parameters,
+        cache,
+        deadline,
+        cancel,
+    } = options;
+    let projects: Vec<Project> = load_records("evvl_projects_v2");
+    let model_configs: Vec<ProjectModelConfig> = load_records("evvl_model_configs_v2");
+    let data_sets: Vec<DataSet> = load_records("evvl_data_sets_v2");
 
     // Find or create project
     let mut project: Option<Project> = if let Some(proj_filter) = project_filter {
@@ -583,16 +1050,34 @@ fn handle_run_command(
     };
 
     // Load prompts (may have been created by get_or_create_repo_project)
-    let mut prompts: Vec<Prompt> = load_from_store("evvl_prompts_v2").unwrap_or_default();
+    let mut prompts: Vec<Prompt> = load_records("evvl_prompts_v2");
 
     // Get prompt content - either from --prompt, stdin, or existing prompt
     let final_prompt: String;
+    let final_system_prompt: Option<String>;
+    let final_messages: Vec<PromptMessage>;
+    let final_tools: Vec<tools::ToolDefinition>;
+    let final_response_schema: Option<Value>;
+    let mut final_parameters: Option<parameters::AIParameters> = None;
     let mut prompt_id: Option<String> = None;
     let mut prompt_version_id: Option<String> = None;
     let mut saved_new_version = false;
 
-    // First, get the raw prompt text from --prompt or stdin
-    let raw_prompt_text = if let Some(text) = prompt_text {
+    if messages.is_some() && prompt_text.is_some() {
+        eprintln!("Error: Use either --prompt or --messages, not both");
+        return 1;
+    }
+    let new_messages = messages.unwrap_or_default();
+    // `--system ""` removes the system prompt from the new version
+    let new_system_prompt: Option<Option<String>> =
+        system_prompt.map(|s| Some(s.to_string()).filter(|s| !s.trim().is_empty()));
+
+    // First, get the raw prompt text from --messages (its last user turn),
+    // --prompt or stdin
+    let raw_prompt_text = if !new_messages.is_empty() {
+        let last_user_turn = new_messages.iter().rev().find(|m| m.role == MessageRole::User);
+        Some(last_user_turn.map(|m| m.content.clone()).unwrap_or_default())
+    } else if let Some(text) = prompt_text {
         Some(text.to_string())
     } else if atty::isnt(atty::Stream::Stdin) {
         let mut stdin = String::new();
@@ -622,7 +1107,7 @@ fn handle_run_command(
         if !prompt_exists {
             if let Some(ref mut proj) = project {
                 if get_or_create_default_prompt(proj, json_output).is_some() {
-                    prompts = load_from_store("evvl_prompts_v2").unwrap_or_default();
+                    prompts = load_records("evvl_prompts_v2");
                 }
             }
         }
@@ -646,50 +1131,68 @@ fn handle_run_command(
 
                 match current_version {
                     Some(cv) => {
-                        // If we have new prompt text, check if it differs from current
-                        if let Some(ref new_content) = raw_prompt_text {
-                            if new_content != &cv.content {
-                                // Content differs - auto-save as new version
-                                let new_version_number = p.versions.iter()
-                                    .map(|v| v.version_number)
-                                    .max()
-                                    .unwrap_or(0) + 1;
-
-                                let new_version_id = uuid::Uuid::new_v4().to_string();
-                                let new_version = PromptVersion {
-                                    id: new_version_id.clone(),
-                                    version_number: new_version_number,
-                                    content: new_content.clone(),
-                                    system_prompt: None,
-                                    parameters: None,
-                                    note: version_note.map(|s| s.to_string()),
-                                    created_at: chrono::Utc::now().timestamp_millis(),
-                                };
-
-                                p.versions.push(new_version);
-                                p.current_version_id = new_version_id.clone();
-                                p.updated_at = chrono::Utc::now().timestamp_millis();
-
-                                // Save updated prompts
-                                if let Err(e) = save_to_store("evvl_prompts_v2", &prompts) {
-                                    eprintln!("Warning: Failed to save new version: {}", e);
-                                } else if !json_output {
-                                    println!("Saved as version {} of prompt '{}'", new_version_number, name);
-                                }
-
-                                final_prompt = new_content.clone();
-                                prompt_version_id = Some(new_version_id);
-                                saved_new_version = true;
-                            } else {
-                                // Content same as current - use existing version
-                                final_prompt = cv.content.clone();
-                                prompt_version_id = Some(cv.id.clone());
+                        // Whatever wasn't given on the command line stays as in the current version
+                        let (content, messages) = match &raw_prompt_text {
+                            Some(new_content) => (new_content.clone(), new_messages.clone()),
+                            None => (cv.content.clone(), cv.messages.clone()),
+                        };
+                        let system_prompt = match &new_system_prompt {
+                            Some(new_system) => new_system.clone(),
+                            None => cv.system_prompt.clone(),
+                        };
+                        let tools = tools.clone().unwrap_or_else(|| cv.tools.clone());
+                        let response_schema = response_schema.clone().or_else(|| cv.response_schema.clone());
+                        // Parameters carry over to an auto-saved version too
+                        final_parameters = cv.parameters.clone();
+
+                        if content != cv.content
+                            || messages != cv.messages
+                            || system_prompt != cv.system_prompt
+                            || tools != cv.tools
+                            || response_schema != cv.response_schema
+                        {
+                            // Content, system prompt, tools or schema differ - auto-save as new version
+                            let new_version_number = p.versions.iter()
+                                .map(|v| v.version_number)
+                                .max()
+                                .unwrap_or(0) + 1;
+
+                            let new_version_id = uuid::Uuid::new_v4().to_string();
+                            let new_version = PromptVersion {
+                                id: new_version_id.clone(),
+                                version_number: new_version_number,
+                                content: content.clone(),
+                                system_prompt: system_prompt.clone(),
+                                messages: messages.clone(),
+                                tools: tools.clone(),
+                                response_schema: response_schema.clone(),
+                                parameters: final_parameters.clone(),
+                                note: version_note.map(|s| s.to_string()),
+                                created_at: chrono::Utc::now().timestamp_millis(),
+                            };
+
+                            p.versions.push(new_version);
+                            p.current_version_id = new_version_id.clone();
+                            p.updated_at = chrono::Utc::now().timestamp_millis();
+
+                            // Save updated prompts
+                            if let Err(e) = save_records("evvl_prompts_v2", &prompts) {
+                                eprintln!("Warning: Failed to save new version: {}", e);
+                            } else if !json_output {
+                                println!("Saved as version {} of prompt '{}'", new_version_number, name);
                             }
+
+                            prompt_version_id = Some(new_version_id);
+                            saved_new_version = true;
                         } else {
-                            // No new content provided - use existing version
-                            final_prompt = cv.content.clone();
+                            // Same as current - use existing version
                             prompt_version_id = Some(cv.id.clone());
                         }
+                        final_prompt = content;
+                        final_system_prompt = system_prompt;
+                        final_messages = messages;
+                        final_tools = tools;
+                        final_response_schema = response_schema;
                     }
                     None => {
                         eprintln!("Error: No current version found for prompt '{}'", name);
@@ -704,6 +1207,10 @@ fn handle_run_command(
         }
     } else if let Some(text) = raw_prompt_text {
         final_prompt = text;
+        final_system_prompt = new_system_prompt.flatten();
+        final_messages = new_messages;
+        final_tools = tools.unwrap_or_default();
+        final_response_schema = response_schema;
     } else {
         eprintln!("Error: No prompt provided. Use --prompt, --prompt-name, or pipe text to stdin.");
         return 1;
@@ -723,43 +1230,41 @@ fn handle_run_command(
             if !json_output {
                 eprintln!("Warning: No model configs in project, using defaults");
             }
-            vec!["anthropic/claude-3-5-sonnet".to_string(), "openai/gpt-4".to_string()]
+            DEFAULT_MODELS.iter().map(|m| m.to_string()).collect()
         } else {
             project_models
         }
     } else {
-        vec!["anthropic/claude-3-5-sonnet".to_string(), "openai/gpt-4".to_string()]
+        DEFAULT_MODELS.iter().map(|m| m.to_string()).collect()
     };
 
     // Determine dataset to use
-    let final_dataset = if no_dataset {
+    let final_dataset: Option<&DataSet> = if no_dataset {
         None
     } else if let Some(ds_name) = dataset_name {
         // Find dataset by name
-        let ds = if let Some(ref proj) = project {
+        if let Some(ref proj) = project {
             data_sets.iter().find(|d| {
                 d.project_id == proj.id &&
                 d.name.to_lowercase() == ds_name.to_lowercase()
             })
         } else {
             data_sets.iter().find(|d| d.name.to_lowercase() == ds_name.to_lowercase())
-        };
-        ds.map(|d| d.name.clone())
+        }
     } else if let Some(ref proj) = project {
         // Use project's first dataset by default
-        data_sets.iter()
-            .find(|d| d.project_id == proj.id)
-            .map(|d| d.name.clone())
+        data_sets.iter().find(|d| d.project_id == proj.id)
     } else {
         None
     };
 
     // Build run config
-    let run_config = json!({
+    let mut run_config = json!({
         "source": "cli",
         "prompt": final_prompt,
+        "systemPrompt": final_system_prompt,
         "models": model_list,
-        "dataset": final_dataset,
+        "dataset": final_dataset.map(|d| d.name.clone()),
         "promptId": prompt_id,
         "promptVersionId": prompt_version_id,
         "projectId": project.as_ref().map(|p| p.id.clone()),
@@ -768,41 +1273,542 @@ fn handle_run_command(
         "status": "pending",
         "savedVersion": saved_new_version
     });
+    if !final_messages.is_empty() {
+        run_config["messages"] = json!(final_messages);
+    }
+    if !final_tools.is_empty() {
+        run_config["tools"] = json!(final_tools);
+    }
+    if let Some(schema) = &final_response_schema {
+        run_config["responseSchema"] = schema.clone();
+    }
+
+    // If --open flag is set, save config for GUI to pick up and let it execute
+    if open_gui {
+        if json_output {
+            println!("{}", serde_json::to_string_pretty(&run_config).unwrap());
+        } else {
+            println!("Run Configuration:");
+            println!("  Prompt: {}", truncate_string(&final_prompt, 60));
+            if let Some(system) = &final_system_prompt {
+                println!("  System: {}", truncate_string(system, 60));
+            }
+            println!("  Models: {}", model_list.join(", "));
+            if let Some(ds) = final_dataset {
+                println!("  Dataset: {}", ds.name);
+            }
+            if let Some(ref proj) = project {
+                println!("  Project: {}", proj.name);
+            }
+            if saved_new_version {
+                println!("  New version saved: yes");
+            }
+        }
+
+        let pending_runs: Vec<Value> = load_from_store("evvl_pending_cli_runs").unwrap_or_default();
+        let mut runs = pending_runs;
+        runs.push(run_config);
+        let _ = save_to_store("evvl_pending_cli_runs", &runs);
+        return 0;
+    }
+
+    // Otherwise execute headlessly
+    let run_model_configs = get_or_create_model_configs(project.as_mut(), &model_list, json_output);
+    warn_unchecked_schema_keywords(final_response_schema.as_ref());
+
+    let plan = runner::RunPlan {
+        project_id: project.as_ref().map(|p| p.id.clone()).unwrap_or_default(),
+        prompt_id: prompt_id.clone().unwrap_or_default(),
+        prompt_version_id: prompt_version_id.clone().unwrap_or_default(),
+        prompt: final_prompt.clone(),
+        system_prompt: final_system_prompt,
+        messages: final_messages,
+        tools: final_tools,
+        response_schema: final_response_schema,
+        version_parameters: final_parameters,
+        cli_parameters,
+        model_configs: run_model_configs,
+        data_set_id: final_dataset.map(|d| d.id.clone()),
+        items: final_dataset.map(|d| d.items.clone()).unwrap_or_default(),
+        concurrency,
+        pricing: pricing::PricingRegistry::load(),
+        cache,
+        deadline: deadline.map(|d| std::time::Instant::now() + d),
+        cancel,
+        // Persist only when the run belongs to a saved prompt version
+        checkpoint: prompt_version_id.is_some().then(|| Box::new(checkpoint_run) as runner::Checkpoint),
+    };
+
+    // Strict mode: refuse to spend tokens on half-rendered prompts
+    if strict {
+        if let Err(e) = runner::check_variables(&plan) {
+            eprintln!("Error: {}", e);
+            return 1;
+        }
+    }
+
+    if !json_output {
+        println!(
+            "Running {} model(s){}...",
+            plan.model_configs.len(),
+            final_dataset
+                .map(|d| format!(" over {} item(s) of '{}'", d.items.len(), d.name))
+                .unwrap_or_default()
+        );
+    }
+
+    let keys = resolve_api_keys();
+    let run = if !stream {
+        runner::execute_run(&plan, &keys, None)
+    } else if json_output {
+        runner::execute_run(&plan, &keys, Some(&progress::print_event))
+    } else if atty::is(atty::Stream::Stderr) {
+        let view = progress::LiveView::new(&plan);
+        view.run(|| runner::execute_run(&plan, &keys, Some(&|event| view.handle(&event))))
+    } else {
+        runner::execute_run(&plan, &keys, None)
+    };
+
+    if prompt_version_id.is_some() {
+        if let Err(e) = save_evaluation_run(&run) {
+            eprintln!("Warning: Failed to save evaluation run: {}", e);
+        }
+    } else if !json_output {
+        eprintln!("Warning: Run not saved (no project or saved prompt)");
+    }
+
+    report_run(&run, &final_prompt, &plan, json_output, stream)
+}
+
+/// Save `run` over the stored run with the same id, or append it. Older
+/// runs are not re-parsed, as they may not all be well formed.
+fn save_evaluation_run(run: &EvaluationRun) -> Result<(), String> {
+    let mut runs: Vec<Value> = load_from_store("evvl_evaluation_runs").unwrap_or_default();
+    let value = serde_json::to_value(run).map_err(|e| e.to_string())?;
+    match runs.iter().position(|r| r.get("id").and_then(|v| v.as_str()) == Some(run.id.as_str())) {
+        Some(index) => runs[index] = value,
+        None => runs.push(value),
+    }
+    save_to_store("evvl_evaluation_runs", &runs)
+}
+
+/// `RunPlan::checkpoint` for runs saved to the store
+fn checkpoint_run(run: &EvaluationRun) {
+    if let Err(e) = save_evaluation_run(run) {
+        eprintln!("Warning: Failed to save run progress: {}", e);
+    }
+}
+
+/// Print a finished run, as text or JSON (closing the event stream when
+/// streaming), and return the exit code for its status
+fn report_run(run: &EvaluationRun, prompt: &str, plan: &runner::RunPlan, json_output: bool, stream: bool) -> i32 {
+    let results: Vec<CliRunResult> =
+        run.results.iter().map(|result| cli_run_result(result, &plan.pricing)).collect();
+    let total_cost = pricing::total_cost(results.iter().map(|r| r.cost));
+    let unpriced = unpriced_results(&results);
 
     if json_output {
-        println!("{}", serde_json::to_string_pretty(&run_config).unwrap());
+        let output = CliRunOutput {
+            id: run.id.clone(),
+            timestamp: run.created_at,
+            prompt: prompt.to_string(),
+            total_cost,
+            unpriced_results: unpriced,
+            tool_pass_rates: tool_pass_rates(&results),
+            schema_conformance: schema_conformance(&results),
+            results,
+            status: run.status.clone(),
+        };
+        if stream {
+            // Closes the NDJSON event stream
+            let mut summary = serde_json::to_value(&output).unwrap();
+            summary["event"] = json!("summary");
+            println!("{}", summary);
+        } else {
+            println!("{}", serde_json::to_string_pretty(&output).unwrap());
+        }
     } else {
-        println!("Run Configuration:");
-        println!("  Prompt: {}", truncate_string(&final_prompt, 60));
-        println!("  Models: {}", model_list.join(", "));
-        if let Some(ref ds) = final_dataset {
-            println!("  Dataset: {}", ds);
+        for result in &results {
+            println!("\n=== {}/{} ===", result.provider, result.model);
+            match (&result.error, &result.content) {
+                (Some(error), _) => println!("Error: {}", error),
+                (None, Some(content)) => println!("{}", content),
+                (None, None) => {}
+            }
+            for call in result.tool_calls.iter().flatten() {
+                println!("Tool call: {}({})", call.name, call.arguments);
+            }
+            for assertion in &result.assertions {
+                println!("{} {}", if assertion.passed { "PASS" } else { "FAIL" }, assertion.message);
+            }
+            match result.schema_errors.as_deref() {
+                Some([]) => println!("PASS output matches the schema"),
+                Some(errors) => errors.iter().for_each(|e| println!("FAIL schema: {}", e)),
+                None => {}
+            }
+            if let Some(latency) = result.latency {
+                let breakdown = match (result.input_tokens, result.output_tokens) {
+                    (Some(input), Some(output)) => format!(": {} in / {} out", input, output),
+                    _ => String::new(),
+                };
+                let cost = result.cost.map(|c| format!(", {}", pricing::format_cost(c))).unwrap_or_default();
+                println!(
+                    "({} ms, {} tokens{}{}{})",
+                    latency,
+                    result.tokens.map(|t| t.to_string()).unwrap_or_else(|| "?".to_string()),
+                    breakdown,
+                    cost,
+                    if result.cached { ", cached" } else { "" }
+                );
+            }
+            if let Some(reason) = result.finish_reason.as_deref().filter(|r| providers::is_truncated(r)) {
+                println!("Warning: output was cut off by the token limit ({})", reason);
+            }
         }
-        if let Some(ref proj) = project {
-            println!("  Project: {}", proj.name);
+        print_pass_rates("Tool call accuracy", &tool_pass_rates(&results));
+        print_pass_rates("Schema conformance", &schema_conformance(&results));
+        let cache_hits = results.iter().filter(|r| r.cached).count();
+        let mut notes = Vec::new();
+        if let Some(cost) = pricing::describe_total(total_cost, unpriced) {
+            notes.push(format!("cost: {}", cost));
         }
-        if saved_new_version {
-            println!("  New version saved: yes");
+        if cache_hits > 0 {
+            notes.push(format!("{} of {} from cache", cache_hits, results.len()));
+        }
+        if run.status == "cancelled" {
+            let cells = plan.model_configs.len() * plan.items.len().max(1);
+            notes.push(format!("{} of {} cells finished", results.len(), cells));
+        }
+        if notes.is_empty() {
+            println!("\nRun {} {}", run.id, run.status);
+        } else {
+            println!("\nRun {} {} ({})", run.id, run.status, notes.join(", "));
         }
-        println!("\nUse --open to execute in GUI.");
     }
 
-    // If --open flag is set, save config for GUI to pick up
-    if open_gui {
-        let pending_runs: Vec<Value> = load_from_store("evvl_pending_cli_runs").unwrap_or_default();
-        let mut runs = pending_runs;
-        runs.push(run_config);
-        let _ = save_to_store("evvl_pending_cli_runs", &runs);
+    match run.status.as_str() {
+        "completed" => 0,
+        // Conventional exit code for a run stopped by SIGINT
+        "cancelled" => 130,
+        _ => 1,
     }
+}
 
-    0
+/// `evvl runs resume <run-id>`: execute the cells of an interrupted run
+/// that are missing or failed, and merge them into the stored run
+fn handle_runs_resume_command(run_id: &str, options: RunOptions) -> i32 {
+    // The prompt, models and dataset come from the run; only how it executes
+    // can be changed
+    let RunOptions { json_output, stream, concurrency, cli_parameters, cache, deadline, cancel, .. } = options;
+    let runs: Vec<Value> = load_from_store("evvl_evaluation_runs").unwrap_or_default();
+    let Some(stored) = runs.iter().find(|r| r.get("id").and_then(|v| v.as_str()) == Some(run_id)) else {
+        eprintln!("Error: Run ID not found");
+        return 1;
+    };
+    let run = match EvaluationRun::from_stored(stored) {
+        Ok(run) => run,
+        Err(e) => {
+            eprintln!("Error: Run {} cannot be read:\n{}", run_id, e);
+            return 1;
+        }
+    };
+    if !matches!(run.status.as_str(), "running" | "cancelled" | "failed") {
+        eprintln!(
+            "Error: Run {} is {}; only running, cancelled or failed runs can be resumed",
+            run_id, run.status
+        );
+        return 1;
+    }
+
+    // Rebuild the plan from what the run was made of
+    let prompts: Vec<Prompt> = load_records("evvl_prompts_v2");
+    let Some(version) = prompts
+        .iter()
+        .find(|p| p.id == run.prompt_id)
+        .and_then(|p| p.versions.iter().find(|v| v.id == run.prompt_version_id))
+    else {
+        eprintln!("Error: The prompt version of run {} no longer exists", run_id);
+        return 1;
+    };
+    let model_configs: Vec<ProjectModelConfig> = load_records("evvl_model_configs_v2");
+    let mut run_model_configs = Vec::new();
+    for id in &run.model_config_ids {
+        match model_configs.iter().find(|c| &c.id == id) {
+            Some(config) => run_model_configs.push(config.clone()),
+            None => {
+                eprintln!("Error: Model config {} of run {} no longer exists", id, run_id);
+                return 1;
+            }
+        }
+    }
+    let items = match &run.data_set_id {
+        None => Vec::new(),
+        Some(data_set_id) => {
+            let data_sets: Vec<DataSet> = load_records("evvl_data_sets_v2");
+            match data_sets.into_iter().find(|d| &d.id == data_set_id) {
+                Some(data_set) => data_set.items,
+                None => {
+                    eprintln!("Error: The dataset of run {} no longer exists", run_id);
+                    return 1;
+                }
+            }
+        }
+    };
+
+    warn_unchecked_schema_keywords(version.response_schema.as_ref());
+    let plan = runner::RunPlan {
+        project_id: run.project_id.clone(),
+        prompt_id: run.prompt_id.clone(),
+        prompt_version_id: run.prompt_version_id.clone(),
+        prompt: version.content.clone(),
+        system_prompt: version.system_prompt.clone(),
+        messages: version.messages.clone(),
+        tools: version.tools.clone(),
+        response_schema: version.response_schema.clone(),
+        version_parameters: version.parameters.clone(),
+        cli_parameters,
+        model_configs: run_model_configs,
+        data_set_id: run.data_set_id.clone(),
+        items,
+        concurrency,
+        pricing: pricing::PricingRegistry::load(),
+        cache,
+        deadline: deadline.map(|d| std::time::Instant::now() + d),
+        cancel,
+        checkpoint: Some(Box::new(checkpoint_run)),
+    };
+
+    let pending = runner::pending_cells(&plan, &run).len();
+    if !json_output {
+        println!("Resuming run {}: {} cell(s) to execute...", run.id, pending);
+    }
+
+    let keys = resolve_api_keys();
+    let resumed = if !stream {
+        runner::resume_run(&plan, run, &keys, None)
+    } else if json_output {
+        runner::resume_run(&plan, run, &keys, Some(&progress::print_event))
+    } else if atty::is(atty::Stream::Stderr) {
+        let view = progress::LiveView::resuming(&plan, &run);
+        view.run(|| runner::resume_run(&plan, run, &keys, Some(&|event| view.handle(&event))))
+    } else {
+        runner::resume_run(&plan, run, &keys, None)
+    };
+
+    if let Err(e) = save_evaluation_run(&resumed) {
+        eprintln!("Warning: Failed to save evaluation run: {}", e);
+    }
+
+    report_run(&resumed, &plan.prompt, &plan, json_output, stream)
+}
+
+/// Build the CLI layer of the parameter merge from `--temperature`,
+/// `--max-tokens` and `--top-p`
+fn cli_parameters(
+    temperature: Option<&str>,
+    max_tokens: Option<&str>,
+    top_p: Option<&str>,
+) -> Result<parameters::AIParameters, String> {
+    fn parse<T: std::str::FromStr>(flag: &str, value: Option<&str>) -> Result<Option<T>, String> {
+        value
+            .map(|v| v.parse::<T>().map_err(|_| format!("--{} got an invalid value '{}'", flag, v)))
+            .transpose()
+    }
+    let params = parameters::AIParameters {
+        temperature: parse("temperature", temperature)?,
+        max_tokens: parse("max-tokens", max_tokens)?,
+        top_p: parse("top-p", top_p)?,
+        ..Default::default()
+    };
+    params.validate()?;
+    Ok(params)
+}
+
+/// Parse a duration flag: `90` or `90s`, `500ms`, `15m`, `2h`
+fn parse_duration(value: &str) -> Result<std::time::Duration, String> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("got an invalid duration '{}' (e.g. 90s, 15m, 2h)", value))?;
+    let seconds = match unit {
+        "ms" => return Ok(std::time::Duration::from_millis(number)),
+        "" | "s" => number,
+        "m" => number * 60,
+        "h" => number * 3600,
+        _ => return Err(format!("got an invalid duration '{}' (e.g. 90s, 15m, 2h)", value)),
+    };
+    Ok(std::time::Duration::from_secs(seconds))
+}
+
+/// The system prompt from `--system` or `--system-file`
+fn system_prompt_flag(system: Option<&str>, system_file: Option<&str>) -> Result<Option<String>, String> {
+    match (system, system_file) {
+        (Some(_), Some(_)) => Err("--system and --system-file cannot be used together".to_string()),
+        (Some(text), None) => Ok(Some(text.to_string())),
+        (None, Some(path)) => std::fs::read_to_string(path)
+            .map(|text| Some(text.trim_end().to_string()))
+            .map_err(|e| format!("--system-file cannot read {}: {}", path, e)),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Read a `--messages` file: a JSON list of `{"role", "content"}` turns
+fn load_messages(path: &str) -> Result<Vec<PromptMessage>, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    let messages: Vec<PromptMessage> = serde_json::from_str(&content)
+        .map_err(|e| format!("{} is not a list of {{\"role\", \"content\"}} messages: {}", path, e))?;
+    if !messages.iter().any(|m| m.role == MessageRole::User) {
+        return Err(format!("{} has no user message", path));
+    }
+    Ok(messages)
+}
+
+/// Read a `--tools` file: a JSON list of `{"name", "description", "parameters"}`
+/// function definitions, `parameters` being a JSON Schema
+fn load_tools(path: &str) -> Result<Vec<tools::ToolDefinition>, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    let tools: Vec<tools::ToolDefinition> = serde_json::from_str(&content)
+        .map_err(|e| format!("{} is not a list of {{\"name\", \"parameters\"}} tools: {}", path, e))?;
+    if let Some(tool) = tools.iter().find(|t| t.name.trim().is_empty()) {
+        return Err(format!("{} has a tool without a name: {}", path, json!(tool)));
+    }
+    Ok(tools)
+}
+
+/// Read a `--schema` file: the JSON Schema outputs must follow
+fn load_schema(path: &str) -> Result<Value, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    let schema: Value = serde_json::from_str(&content).map_err(|e| format!("{} is not JSON: {}", path, e))?;
+    if !schema.is_object() {
+        return Err(format!("{} is not a JSON Schema object", path));
+    }
+    Ok(schema)
+}
+
+/// Set up where responses come from for `--no-cache`, `--refresh`,
+/// `--record` and `--replay`: the response cache to use, and whether the
+/// run is being recorded (so the cassette gets saved afterwards). Cassette
+/// runs must reach the provider layer on every call, so they get no cache.
+fn response_sources(
+    no_cache: bool,
+    refresh: bool,
+    record: Option<&str>,
+    replay: Option<&str>,
+) -> Result<(Option<cache::ResponseCache>, bool), String> {
+    let recording = install_cassette(record, replay)?;
+    let cache = (!no_cache && record.is_none() && replay.is_none()).then(|| cache::ResponseCache::open(refresh));
+    Ok((cache, recording))
+}
+
+/// Install the cassette for `--record`/`--replay`. Returns whether the run
+/// is being recorded, so the cassette gets saved afterwards.
+fn install_cassette(record: Option<&str>, replay: Option<&str>) -> Result<bool, String> {
+    match (record, replay) {
+        (Some(_), Some(_)) => Err("--record and --replay cannot be used together".to_string()),
+        (Some(path), None) => {
+            providers::cassette::install(providers::cassette::Cassette::record(std::path::Path::new(path)));
+            Ok(true)
+        }
+        (None, Some(path)) => {
+            providers::cassette::install(providers::cassette::Cassette::replay(std::path::Path::new(path))?);
+            Ok(false)
+        }
+        (None, None) => Ok(false),
+    }
+}
+
+/// Flatten a stored result into the CLI output shape. Results saved without
+/// a cost (older runs, GUI runs) are priced from their token counts.
+fn cli_run_result(result: &EvaluationResult, pricing: &pricing::PricingRegistry) -> CliRunResult {
+    let output = &result.output;
+    CliRunResult {
+        model: output.model_config.model.clone(),
+        provider: output.model_config.provider.clone(),
+        content: Some(output.content.clone()),
+        tokens: output.tokens,
+        input_tokens: output.input_tokens,
+        output_tokens: output.output_tokens,
+        cached_tokens: output.cached_tokens,
+        reasoning_tokens: output.reasoning_tokens,
+        finish_reason: output.finish_reason.clone(),
+        latency: output.latency,
+        cost: pricing.output_cost(output),
+        cached: output.cached.unwrap_or(false),
+        error: result.error.clone().or_else(|| output.error.clone()),
+        tool_calls: output.tool_calls.clone(),
+        assertions: result.assertions.clone(),
+        schema_errors: result.schema_errors.clone(),
+    }
+}
+
+/// Results that succeeded but whose cost isn't known (no price for the
+/// model, or no token counts); failed calls aren't billed
+fn unpriced_results(results: &[CliRunResult]) -> usize {
+    results.iter().filter(|r| r.cost.is_none() && r.error.is_none()).count()
+}
+
+/// Per model (`provider/model`), in order of appearance. `outcome` is
+/// `None` for a result not graded.
+fn pass_rates(results: &[CliRunResult], outcome: impl Fn(&CliRunResult) -> Option<bool>) -> Vec<CliPassRate> {
+    let mut summary: Vec<CliPassRate> = Vec::new();
+    for result in results {
+        let Some(passed) = outcome(result) else {
+            continue;
+        };
+        let model = format!("{}/{}", result.provider, result.model);
+        let index = match summary.iter().position(|rate| rate.model == model) {
+            Some(index) => index,
+            None => {
+                summary.push(CliPassRate { model, passed: 0, graded: 0, rate: 0.0 });
+                summary.len() - 1
+            }
+        };
+        let rate = &mut summary[index];
+        if passed {
+            rate.passed += 1;
+        }
+        rate.graded += 1;
+        rate.rate = rate.passed as f64 / rate.graded as f64;
+    }
+    summary
+}
+
+/// Results graded by tool call assertions pass when every assertion does
+fn tool_pass_rates(results: &[CliRunResult]) -> Vec<CliPassRate> {
+    pass_rates(results, |r| (!r.assertions.is_empty()).then(|| r.assertions.iter().all(|a| a.passed)))
+}
+
+fn schema_conformance(results: &[CliRunResult]) -> Vec<CliPassRate> {
+    pass_rates(results, |r| r.schema_errors.as_ref().map(Vec::is_empty))
+}
+
+fn print_pass_rates(title: &str, summary: &[CliPassRate]) {
+    if summary.is_empty() {
+        return;
+    }
+    println!("\n{}:", title);
+    for rate in summary {
+        println!("  {}: {}/{} passed ({:.0}%)", rate.model, rate.passed, rate.graded, rate.rate * 100.0);
+    }
+}
+
+/// Warn about response schema keywords the conformance check can't enforce,
+/// so a passing output isn't taken for more than it is
+fn warn_unchecked_schema_keywords(schema: Option<&Value>) {
+    let unchecked = schema.map(schema::unsupported_keywords).unwrap_or_default();
+    if !unchecked.is_empty() {
+        eprintln!("Warning: schema conformance does not check {}", unchecked.join(", "));
+    }
 }
 
 fn truncate_string(s: &str, max_len: usize) -> String {
-    if s.len() <= max_len {
+    if s.chars().count() <= max_len {
         s.to_string()
     } else {
-        format!("{}...", &s[..max_len - 3])
+        let truncated: String = s.chars().take(max_len - 3).collect();
+        format!("{}...", truncated)
     }
 }
 
@@ -974,6 +1980,73 @@ fn get_env_api_keys() -> ApiKeys {
     }
 }
 
+/// Cancel tokens of the GUI generations in flight, by request ID
+fn gui_generations() -> &'static std::sync::Mutex<HashMap<String, providers::CancelToken>> {
+    static GENERATIONS: std::sync::OnceLock<std::sync::Mutex<HashMap<String, providers::CancelToken>>> =
+        std::sync::OnceLock::new();
+    GENERATIONS.get_or_init(Default::default)
+}
+
+/// Generate text through the Rust providers (same path as `evvl run`).
+/// With a `requestId`, the call can be stopped with `cancel_generation`.
+/// An `apiKey` (the one set in the GUI) takes priority over the resolved keys.
+#[tauri::command(async)]
+fn generate_text(
+    provider: String,
+    request: providers::ProviderRequest,
+    request_id: Option<String>,
+    api_key: Option<String>,
+) -> providers::ProviderResponse {
+    let mut keys = resolve_api_keys();
+    if let Some(key) = api_key {
+        match provider.as_str() {
+            "openai" => keys.openai = Some(key),
+            "anthropic" => keys.anthropic = Some(key),
+            "openrouter" => keys.openrouter = Some(key),
+            "gemini" => keys.gemini = Some(key),
+            _ => {}
+        }
+    }
+    let cancel = providers::CancelToken::default();
+    if let Some(id) = &request_id {
+        gui_generations().lock().unwrap().insert(id.clone(), cancel.clone());
+    }
+    let response = providers::generate_cancellable(
+        &provider,
+        &keys,
+        &request,
+        &providers::RetryPolicy::default(),
+        &cancel,
+        None,
+    );
+    if let Some(id) = &request_id {
+        gui_generations().lock().unwrap().remove(id);
+    }
+    response
+}
+
+/// Cancel a `generate_text` call: it returns at once with a `cancelled`
+/// error, and its request closes the connection at the next streamed chunk
+/// (see `providers::cancel`). Returns whether the request was still in flight.
+#[tauri::command]
+fn cancel_generation(request_id: String) -> bool {
+    match gui_generations().lock().unwrap().remove(&request_id) {
+        Some(cancel) => {
+            cancel.cancel();
+            true
+        }
+        None => false,
+    }
+}
+
+/// List models installed in the local Ollama daemon (honors OLLAMA_HOST)
+#[tauri::command(async)]
+fn list_ollama_models() -> Result<Vec<providers::OllamaModel>, String> {
+    providers::OllamaProvider::from_env()
+        .list_models()
+        .map_err(|e| format!("Ollama {}", e))
+}
+
 #[derive(Debug, Serialize)]
 struct CliInstallResult {
     success: bool,
@@ -1240,6 +2313,24 @@ fn install_cli() -> CliInstallResult {
 // Main Entry Point
 // ============================================================================
 
+/// Cancel token for a CLI run, cancelled by the first Ctrl-C so the results
+/// finished so far get saved. A second Ctrl-C quits at once.
+fn cancel_on_interrupt() -> providers::CancelToken {
+    let cancel = providers::CancelToken::default();
+    let handler_token = cancel.clone();
+    let installed = ctrlc::set_handler(move || {
+        if handler_token.is_cancelled() {
+            std::process::exit(130);
+        }
+        eprintln!("\nCancelling, finished results will be saved (Ctrl-C again to quit now)");
+        handler_token.cancel();
+    });
+    if let Err(e) = installed {
+        eprintln!("Warning: Ctrl-C will not save partial results: {}", e);
+    }
+    cancel
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
@@ -1263,6 +2354,9 @@ pub fn run() {
             set_store_data,
             get_pending_cli_runs,
             get_env_api_keys,
+            generate_text,
+            cancel_generation,
+            list_ollama_models,
             install_cli,
             check_cli_installed
         ])
@@ -1294,6 +2388,10 @@ pub fn run() {
                 .map(|a| a.occurrences > 0)
                 .unwrap_or(false);
 
+            let stream = !cli_matches.args.get("no-stream")
+                .map(|a| a.occurrences > 0)
+                .unwrap_or(false);
+
             let project_filter = cli_matches.args.get("project")
                 .and_then(|a| a.value.as_str())
                 .map(|s| s.to_string());
@@ -1333,6 +2431,10 @@ pub fn run() {
                             should_run_gui = open_gui;
                         }
                     }
+                    "models" => {
+                        exit_code = handle_models_command(json_output);
+                        should_run_gui = open_gui;
+                    }
                     "export" => {
                         let run_id = matches.args.get("run")
                             .and_then(|a| a.value.as_str());
@@ -1341,9 +2443,129 @@ pub fn run() {
                         exit_code = handle_export_command(run_id, format, json_output);
                         should_run_gui = open_gui;
                     }
+                    "runs" => {
+                        if let Some(sub_subcommand_matches) = &matches.subcommand {
+                            if sub_subcommand_matches.name == "resume" {
+                                let resume_matches = &sub_subcommand_matches.matches;
+                                let run_id = resume_matches.args.get("run-id")
+                                    .and_then(|a| a.value.as_str())
+                                    .unwrap_or_default();
+                                let concurrency = match resume_matches.args.get("concurrency")
+                                    .and_then(|a| a.value.as_str())
+                                {
+                                    None => Some(runner::DEFAULT_CONCURRENCY),
+                                    Some(value) => value.parse::<usize>().ok().filter(|n| *n > 0),
+                                };
+                                let Some(concurrency) = concurrency else {
+                                    eprintln!("Error: --concurrency must be a positive integer");
+                                    std::process::exit(1);
+                                };
+                                let deadline = match resume_matches.args.get("deadline")
+                                    .and_then(|a| a.value.as_str())
+                                    .map(parse_duration)
+                                    .transpose()
+                                {
+                                    Ok(deadline) => deadline,
+                                    Err(e) => {
+                                        eprintln!("Error: --deadline {}", e);
+                                        std::process::exit(1);
+                                    }
+                                };
+                                let cli_parameters = match cli_parameters(
+                                    resume_matches.args.get("temperature").and_then(|a| a.value.as_str()),
+                                    resume_matches.args.get("max-tokens").and_then(|a| a.value.as_str()),
+                                    resume_matches.args.get("top-p").and_then(|a| a.value.as_str()),
+                                ) {
+                                    Ok(params) => params,
+                                    Err(e) => {
+                                        eprintln!("Error: {}", e);
+                                        std::process::exit(1);
+                                    }
+                                };
+
+                                let (cache, recording) = match response_sources(
+                                    resume_matches.args.get("no-cache").is_some_and(|a| a.occurrences > 0),
+                                    resume_matches.args.get("refresh").is_some_and(|a| a.occurrences > 0),
+                                    resume_matches.args.get("record").and_then(|a| a.value.as_str()),
+                                    resume_matches.args.get("replay").and_then(|a| a.value.as_str()),
+                                ) {
+                                    Ok(sources) => sources,
+                                    Err(e) => {
+                                        eprintln!("Error: {}", e);
+                                        std::process::exit(1);
+                                    }
+                                };
+
+                                exit_code = handle_runs_resume_command(run_id, RunOptions {
+                                    json_output,
+                                    stream,
+                                    concurrency,
+                                    cli_parameters,
+                                    cache,
+                                    deadline,
+                                    cancel: cancel_on_interrupt(),
+                                    ..Default::default()
+                                });
+                                if recording {
+                                    if let Err(e) = providers::cassette::save_active() {
+                                        eprintln!("Error: {}", e);
+                                        exit_code = 1;
+                                    }
+                                }
+                                should_run_gui = open_gui;
+                            }
+                        } else {
+                            eprintln!("Error: Missing subcommand. Usage: evvl runs resume <run-id>");
+                            exit_code = 1;
+                            should_run_gui = false;
+                        }
+                    }
                     "run" => {
                         let prompt_text = matches.args.get("prompt")
                             .and_then(|a| a.value.as_str());
+                        let system_prompt = match system_prompt_flag(
+                            matches.args.get("system").and_then(|a| a.value.as_str()),
+                            matches.args.get("system-file").and_then(|a| a.value.as_str()),
+                        ) {
+                            Ok(system_prompt) => system_prompt,
+                            Err(e) => {
+                                eprintln!("Error: {}", e);
+                                std::process::exit(1);
+                            }
+                        };
+                        let messages = match matches.args.get("messages")
+                            .and_then(|a| a.value.as_str())
+                            .map(load_messages)
+                            .transpose()
+                        {
+                            Ok(messages) => messages,
+                            Err(e) => {
+                                eprintln!("Error: --messages {}", e);
+                                std::process::exit(1);
+                            }
+                        };
+                        let tools = match matches.args.get("tools")
+                            .and_then(|a| a.value.as_str())
+                            .map(load_tools)
+                            .transpose()
+                        {
+                            Ok(tools) => tools,
+                            Err(e) => {
+                                eprintln!("Error: --tools {}", e);
+                                std::process::exit(1);
+                            }
+                        };
+                        let response_schema = match matches.args.get("schema")
+                            .and_then(|a| a.value.as_str())
+                            .map(load_schema)
+                            .transpose()
+                        {
+                            Ok(schema) => schema,
+                            Err(e) => {
+                                eprintln!("Error: --schema {}", e);
+                                std::process::exit(1);
+                            }
+                        };
                         let prompt_name = matches.args.get("prompt-name")
                             .and_then(|a| a.value.as_str());
                         let version_note = matches.args.get("version-note")
@@ -1355,18 +2577,82 @@ pub fn run() {
                         let no_dataset = matches.args.get("no-dataset")
                             .map(|a| a.occurrences > 0)
                             .unwrap_or(false);
+                        let concurrency = match matches.args.get("concurrency")
+                            .and_then(|a| a.value.as_str())
+                        {
+                            None => Some(runner::DEFAULT_CONCURRENCY),
+                            Some(value) => value.parse::<usize>().ok().filter(|n| *n > 0),
+                        };
+                        let Some(concurrency) = concurrency else {
+                            eprintln!("Error: --concurrency must be a positive integer");
+                            std::process::exit(1);
+                        };
+                        let strict = matches.args.get("strict")
+                            .map(|a| a.occurrences > 0)
+                            .unwrap_or(false);
+                        let (cache, recording) = match response_sources(
+                            matches.args.get("no-cache").is_some_and(|a| a.occurrences > 0),
+                            matches.args.get("refresh").is_some_and(|a| a.occurrences > 0),
+                            matches.args.get("record").and_then(|a| a.value.as_str()),
+                            matches.args.get("replay").and_then(|a| a.value.as_str()),
+                        ) {
+                            Ok(sources) => sources,
+                            Err(e) => {
+                                eprintln!("Error: {}", e);
+                                std::process::exit(1);
+                            }
+                        };
+                        let deadline = match matches.args.get("deadline")
+                            .and_then(|a| a.value.as_str())
+                            .map(parse_duration)
+                            .transpose()
+                        {
+                            Ok(deadline) => deadline,
+                            Err(e) => {
+                                eprintln!("Error: --deadline {}", e);
+                                std::process::exit(1);
+                            }
+                        };
+                        let cli_parameters = match cli_parameters(
+                            matches.args.get("temperature").and_then(|a| a.value.as_str()),
+                            matches.args.get("max-tokens").and_then(|a| a.value.as_str()),
+                            matches.args.get("top-p").and_then(|a| a.value.as_str()),
+                        ) {
+                            Ok(params) => params,
+                            Err(e) => {
+                                eprintln!("Error: {}", e);
+                                std::process::exit(1);
+                            }
+                        };
 
-                        exit_code = handle_run_command(
+                        exit_code = handle_run_command(RunOptions {
                             prompt_text,
+                            system_prompt: system_prompt.as_deref(),
+                            messages,
+                            tools,
+                            response_schema,
                             prompt_name,
                             version_note,
                             models,
-                            dataset,
+                            dataset_name: dataset,
                             no_dataset,
-                            project_filter.as_deref(),
+                            project_filter: project_filter.as_deref(),
                             json_output,
-                            open_gui
-                        );
+                            open_gui,
+                            stream,
+                            concurrency,
+                            strict,
+                            cli_parameters,
+                            cache,
+                            deadline,
+                            cancel: cancel_on_interrupt(),
+                        });
+                        if recording {
+                            if let Err(e) = providers::cassette::save_active() {
+                                eprintln!("Error: {}", e);
+                                exit_code = 1;
+                            }
+                        }
                         should_run_gui = open_gui;
                     }
                     _ => {}
@@ -1391,17 +2677,16 @@ pub fn run() {
 
                 if let Some(prompt_text) = positional_prompt {
                     // Run with the positional prompt - auto-detect git repo as project
-                    exit_code = handle_run_command(
-                        Some(prompt_text.as_str()),
-                        None,  // prompt_name
-                        None,  // version_note
-                        None,  // models (use project defaults)
-                        None,  // dataset (use project default)
-                        false, // no_dataset
-                        project_filter.as_deref(),
+                    exit_code = handle_run_command(RunOptions {
+                        prompt_text: Some(prompt_text.as_str()),
+                        project_filter: project_filter.as_deref(),
                         json_output,
-                        open_gui
-                    );
+                        open_gui,
+                        stream,
+                        cache: Some(cache::ResponseCache::open(false)),
+                        cancel: cancel_on_interrupt(),
+                        ..Default::default()
+                    });
                     should_run_gui = open_gui;
                 }
             }
@@ -1519,7 +2804,10 @@ mod tests {
             version_number: 1,
             content: "Hello {{name}}!".to_string(),
             system_prompt: Some("You are helpful".to_string()),
-            parameters: Some(json!({"temperature": 0.7})),
+            messages: Vec::new(),
+            tools: Vec::new(),
+            response_schema: None,
+            parameters: Some(parameters::AIParameters { temperature: Some(0.7), ..Default::default() }),
             note: None,
             created_at: 1700000000000,
         };
@@ -1532,6 +2820,68 @@ mod tests {
         assert_eq!(parsed.system_prompt, Some("You are helpful".to_string()));
     }
 
+    #[test]
+    fn test_prompt_version_response_schema_round_trip() {
+        let version = PromptVersion {
+            id: "ver-1".to_string(),
+            version_number: 1,
+            content: "Greet {{name}} as JSON".to_string(),
+            system_prompt: None,
+            messages: Vec::new(),
+            tools: Vec::new(),
+            response_schema: Some(json!({ "type": "object", "required": ["greeting"] })),
+            parameters: None,
+            note: None,
+            created_at: 1700000000000,
+        };
+
+        let json = serde_json::to_string(&version).unwrap();
+        assert!(json.contains("\"responseSchema\":{"));
+        let parsed: PromptVersion = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.response_schema, version.response_schema);
+
+        // Versions saved before response schemas existed have none
+        let without = serde_json::to_string(&PromptVersion { response_schema: None, ..version }).unwrap();
+        assert!(!without.contains("responseSchema"));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        use std::time::Duration;
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("15m").unwrap(), Duration::from_secs(900));
+        assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert!(parse_duration("soon").is_err());
+        assert!(parse_duration("5d").is_err());
+    }
+
+    #[test]
+    fn test_system_prompt_flag() {
+        assert_eq!(system_prompt_flag(Some("Be terse"), None).unwrap().as_deref(), Some("Be terse"));
+        assert_eq!(system_prompt_flag(None, None).unwrap(), None);
+        assert!(system_prompt_flag(Some("Be terse"), Some("system.txt")).is_err());
+
+        let path = std::env::temp_dir().join(format!("evvl-system-{}.txt", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "You review code.\n").unwrap();
+        let from_file = system_prompt_flag(None, path.to_str()).unwrap();
+        assert_eq!(from_file.as_deref(), Some("You review code."));
+        let _ = std::fs::remove_file(path);
+        assert!(system_prompt_flag(None, Some("/nonexistent/system.txt")).is_err());
+    }
+
+    #[test]
+    fn test_cli_parameters() {
+        let params = cli_parameters(Some("0.5"), Some("256"), None).unwrap();
+        assert_eq!(params.temperature, Some(0.5));
+        assert_eq!(params.max_tokens, Some(256));
+        assert_eq!(params.top_p, None);
+
+        assert!(cli_parameters(Some("hot"), None, None).unwrap_err().contains("--temperature"));
+        assert!(cli_parameters(None, Some("-1"), None).is_err());
+        assert!(cli_parameters(None, None, Some("1.5")).unwrap_err().contains("topP"));
+    }
+
     #[test]
     fn test_prompt_serialization() {
         let prompt = Prompt {
@@ -1544,6 +2894,9 @@ mod tests {
                 version_number: 1,
                 content: "Test content".to_string(),
                 system_prompt: None,
+                messages: Vec::new(),
+                tools: Vec::new(),
+                response_schema: None,
                 parameters: None,
                 note: None,
                 created_at: 1700000000000,
@@ -1627,6 +2980,7 @@ mod tests {
             id: "item-1".to_string(),
             name: Some("Test Item".to_string()),
             variables,
+            expected_tool_calls: None,
         };
 
         let json = serde_json::to_string(&item).unwrap();
@@ -1634,6 +2988,18 @@ mod tests {
 
         assert_eq!(parsed.id, "item-1");
         assert_eq!(parsed.variables.get("name"), Some(&"Alice".to_string()));
+        assert!(!json.contains("expectedToolCalls"));
+
+        let parsed: DataSetItem = serde_json::from_value(json!({
+            "id": "item-2",
+            "name": null,
+            "variables": {},
+            "expectedToolCalls": [{ "name": "get_weather", "arguments": { "city": "Paris" } }]
+        }))
+        .unwrap();
+        let expected = parsed.expected_tool_calls.unwrap();
+        assert_eq!(expected[0].name, "get_weather");
+        assert_eq!(expected[0].arguments, Some(json!({ "city": "Paris" })));
     }
 
     #[test]
@@ -1677,6 +3043,9 @@ mod tests {
                 version_number: 1,
                 content: "First".to_string(),
                 system_prompt: None,
+                messages: Vec::new(),
+                tools: Vec::new(),
+                response_schema: None,
                 parameters: None,
                 note: None,
                 created_at: 1700000000000,
@@ -1686,6 +3055,9 @@ mod tests {
                 version_number: 2,
                 content: "Second".to_string(),
                 system_prompt: None,
+                messages: Vec::new(),
+                tools: Vec::new(),
+                response_schema: None,
                 parameters: None,
                 note: None,
                 created_at: 1700000001000,
@@ -1746,4 +3118,152 @@ mod tests {
         assert_eq!(run_config["systemPrompt"], "You are helpful");
         assert_eq!(run_config["savedVersion"], true);
     }
+
+    #[test]
+    fn test_default_anthropic_model_reaches_the_api_as_a_valid_id() {
+        use providers::{test_server, Provider};
+
+        let (provider, model) = runner::parse_model_spec(DEFAULT_MODELS[0]);
+        assert_eq!(provider, "anthropic");
+        let server = test_server::respond(200, r#"{"content":[{"type":"text","text":"ok"}],"usage":{}}"#);
+        let anthropic = providers::AnthropicProvider::with_base_url(&server.base_url("/v1"), "sk-ant".to_string());
+        let request = providers::ProviderRequest { model, prompt: "Hi".to_string(), ..Default::default() };
+
+        anthropic.complete(&request).unwrap();
+        assert_eq!(server.request().body["model"], "claude-3-5-sonnet-latest");
+    }
+
+    #[test]
+    fn test_malformed_stored_record_is_skipped() {
+        let config = |id: &str, parameters: Value| {
+            json!({
+                "id": id,
+                "projectId": "proj-1",
+                "name": "gpt-4o",
+                "provider": "openai",
+                "model": "gpt-4o",
+                "parameters": parameters,
+                "createdAt": 1700000000000i64
+            })
+        };
+        let stored = vec![
+            config("cfg-1", json!({ "temperature": 0.2 })),
+            config("cfg-2", json!({ "temperature": "warm" })),
+            config("cfg-3", Value::Null),
+        ];
+
+        let (configs, problems) = parse_records::<ProjectModelConfig>(stored.clone());
+        let ids: Vec<&str> = configs.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["cfg-1", "cfg-3"]);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("#2 (id cfg-2): "), "{}", problems[0]);
+
+        // Saving the readable ones back doesn't lose the other
+        let saved = with_unreadable(&configs[..1], stored).unwrap();
+        let ids: Vec<&str> = saved.iter().filter_map(|c| c["id"].as_str()).collect();
+        assert_eq!(ids, vec!["cfg-1", "cfg-2"]);
+    }
+
+    fn stored_run(results: Value) -> Value {
+        json!({
+            "id": "run-1",
+            "projectId": "proj-1",
+            "promptId": "prompt-1",
+            "promptVersionId": "ver-1",
+            "modelConfigIds": ["cfg-1"],
+            "results": results,
+            "status": "completed",
+            "createdAt": 1700000000000i64
+        })
+    }
+
+    #[test]
+    fn test_evaluation_run_from_gui_record() {
+        // Shape written by the web app: no CLI-only fields, optional rating
+        let stored = stored_run(json!([{
+            "id": "res-1",
+            "modelConfigId": "cfg-1",
+            "output": {
+                "id": "out-1",
+                "modelConfig": { "provider": "openai", "model": "gpt-4", "label": "GPT-4" },
+                "type": "text",
+                "content": "Hello",
+                "tokens": 12,
+                "latency": 800,
+                "timestamp": 1700000000001i64
+            },
+            "rating": { "outputId": "out-1", "score": 4 }
+        }]));
+
+        let run = EvaluationRun::from_stored(&stored).unwrap();
+        assert_eq!(run.results.len(), 1);
+        let result = &run.results[0];
+        assert_eq!(result.output.model_config.model, "gpt-4");
+        assert_eq!(result.output.tokens, Some(12));
+        assert_eq!(result.rating.as_ref().unwrap().score, 4);
+        assert_eq!(result.data_set_item_id, None);
+
+        // Optional fields stay absent when written back
+        let written = serde_json::to_value(result).unwrap();
+        assert!(written.get("error").is_none());
+        assert!(written.get("assertions").is_none());
+        assert!(written["output"].get("cost").is_none());
+        assert!(written["output"].get("toolCalls").is_none());
+    }
+
+    #[test]
+    fn test_pass_rates_per_model() {
+        let result = |model: &str, passed: &[bool]| CliRunResult {
+            model: model.to_string(),
+            provider: "openai".to_string(),
+            content: None,
+            tokens: None,
+            input_tokens: None,
+            output_tokens: None,
+            cached_tokens: None,
+            reasoning_tokens: None,
+            finish_reason: None,
+            latency: None,
+            cost: None,
+            cached: false,
+            error: None,
+            tool_calls: None,
+            schema_errors: None,
+            assertions: passed
+                .iter()
+                .map(|&passed| tools::AssertionResult {
+                    kind: "tool_name".to_string(),
+                    passed,
+                    message: String::new(),
+                })
+                .collect(),
+        };
+        let results = vec![
+            result("gpt-4o", &[true, true]),
+            result("gpt-4o-mini", &[true, false]),
+            result("gpt-4o", &[false]),
+            // Not graded: the item expects nothing
+            result("gpt-4o-mini", &[]),
+        ];
+        let rate = |model: &str, passed, graded, rate| CliPassRate { model: model.to_string(), passed, graded, rate };
+        assert_eq!(
+            tool_pass_rates(&results),
+            vec![rate("openai/gpt-4o", 1, 2, 0.5), rate("openai/gpt-4o-mini", 0, 1, 0.0)]
+        );
+        assert!(schema_conformance(&results).is_empty());
+    }
+
+    #[test]
+    fn test_evaluation_run_reports_malformed_results() {
+        let stored = stored_run(json!([
+            { "id": "res-1", "modelConfigId": "cfg-1" },
+            { "id": "res-2", "modelConfigId": "cfg-1", "output": { "id": "out-2" } }
+        ]));
+
+        let problems = EvaluationRun::from_stored(&stored).unwrap_err();
+        let lines: Vec<&str> = problems.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("result #1 (id res-1): missing field `output`"));
+        assert!(lines[1].starts_with("result #2 (id res-2): missing field `modelConfig`"));
+    }
 }
@@ -0,0 +1,160 @@
+//! Generation parameters (`AIParameters` in lib/types.ts), their valid
+//! ranges and how the layers that can set them are merged.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AIParameters {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+
+    // Image-specific
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_size: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_quality: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_style: Option<String>,
+
+    /// Provider-specific settings passed through untouched, e.g.
+    /// OpenRouter's `provider` routing preferences
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl AIParameters {
+    /// Values a provider needs even when nobody set them
+    pub fn provider_defaults(provider: &str) -> Self {
+        match provider {
+            "anthropic" => Self {
+                max_tokens: Some(crate::providers::ANTHROPIC_DEFAULT_MAX_TOKENS),
+                ..Default::default()
+            },
+            _ => Self::default(),
+        }
+    }
+
+    /// Layer `higher` on top of `self`: every field `higher` sets wins
+    pub fn overlay(mut self, higher: &AIParameters) -> Self {
+        fn pick<T: Clone>(low: &mut Option<T>, high: &Option<T>) {
+            if high.is_some() {
+                *low = high.clone();
+            }
+        }
+        pick(&mut self.temperature, &higher.temperature);
+        pick(&mut self.max_tokens, &higher.max_tokens);
+        pick(&mut self.top_p, &higher.top_p);
+        pick(&mut self.frequency_penalty, &higher.frequency_penalty);
+        pick(&mut self.presence_penalty, &higher.presence_penalty);
+        pick(&mut self.stop, &higher.stop);
+        pick(&mut self.image_size, &higher.image_size);
+        pick(&mut self.image_quality, &higher.image_quality);
+        pick(&mut self.image_style, &higher.image_style);
+        for (key, value) in &higher.extra {
+            self.extra.insert(key.clone(), value.clone());
+        }
+        self
+    }
+
+    /// Merge with precedence CLI flags > model config > prompt version >
+    /// provider defaults
+    pub fn merged(
+        provider: &str,
+        prompt_version: Option<&AIParameters>,
+        model_config: Option<&AIParameters>,
+        cli: &AIParameters,
+    ) -> Self {
+        let mut merged = Self::provider_defaults(provider);
+        for layer in [prompt_version, model_config, Some(cli)].into_iter().flatten() {
+            merged = merged.overlay(layer);
+        }
+        merged
+    }
+
+    /// Check every value against the range the providers accept
+    pub fn validate(&self) -> Result<(), String> {
+        fn range(name: &str, value: Option<f64>, min: f64, max: f64) -> Result<(), String> {
+            match value {
+                Some(v) if !(min..=max).contains(&v) => {
+                    Err(format!("{} must be between {} and {} (got {})", name, min, max, v))
+                }
+                _ => Ok(()),
+            }
+        }
+        range("temperature", self.temperature, 0.0, 2.0)?;
+        range("topP", self.top_p, 0.0, 1.0)?;
+        range("frequencyPenalty", self.frequency_penalty, -2.0, 2.0)?;
+        range("presencePenalty", self.presence_penalty, -2.0, 2.0)?;
+        if self.max_tokens == Some(0) {
+            return Err("maxTokens must be greater than 0".to_string());
+        }
+        if let Some(quality) = self.image_quality.as_deref().filter(|q| !["standard", "hd"].contains(q)) {
+            return Err(format!("imageQuality must be 'standard' or 'hd' (got '{}')", quality));
+        }
+        if let Some(style) = self.image_style.as_deref().filter(|s| !["vivid", "natural"].contains(s)) {
+            return Err(format!("imageStyle must be 'vivid' or 'natural' (got '{}')", style));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_round_trips_typescript_shape() {
+        let value = json!({
+            "temperature": 0.7,
+            "maxTokens": 512,
+            "stop": ["END"],
+            "imageQuality": "hd",
+            "provider": { "order": ["anthropic"] }
+        });
+        let params: AIParameters = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(params.max_tokens, Some(512));
+        assert_eq!(params.extra["provider"]["order"][0], "anthropic");
+        assert_eq!(serde_json::to_value(&params).unwrap(), value);
+    }
+
+    #[test]
+    fn test_merge_precedence() {
+        let version = AIParameters { temperature: Some(0.2), top_p: Some(0.5), ..Default::default() };
+        let config = AIParameters { temperature: Some(0.9), max_tokens: Some(100), ..Default::default() };
+        let cli = AIParameters { max_tokens: Some(50), ..Default::default() };
+
+        let merged = AIParameters::merged("anthropic", Some(&version), Some(&config), &cli);
+        assert_eq!(merged.temperature, Some(0.9));
+        assert_eq!(merged.top_p, Some(0.5));
+        assert_eq!(merged.max_tokens, Some(50));
+
+        let merged = AIParameters::merged("anthropic", None, None, &AIParameters::default());
+        assert_eq!(merged.max_tokens, Some(4096));
+    }
+
+    #[test]
+    fn test_validate_ranges() {
+        assert!(AIParameters { temperature: Some(2.0), ..Default::default() }.validate().is_ok());
+        let err = AIParameters { temperature: Some(2.5), ..Default::default() }.validate().unwrap_err();
+        assert!(err.contains("temperature must be between 0 and 2"));
+        assert!(AIParameters { top_p: Some(1.5), ..Default::default() }.validate().is_err());
+        assert!(AIParameters { presence_penalty: Some(-3.0), ..Default::default() }.validate().is_err());
+        assert!(AIParameters { max_tokens: Some(0), ..Default::default() }.validate().is_err());
+        assert!(AIParameters { image_style: Some("noir".to_string()), ..Default::default() }
+            .validate()
+            .is_err());
+    }
+}
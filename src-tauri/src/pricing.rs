@@ -0,0 +1,266 @@
+//! Per-model token prices, used to put a dollar figure on results whose
+//! provider doesn't report the billed cost itself.
+//!
+//! Shipped prices can be overridden (or extended) in the store under
+//! `evvl_model_pricing`, keyed by `provider/model`:
+//! `{ "openai/gpt-4o": { "input": 2.5, "output": 10, "cachedInput": 1.25 } }`
+
+use crate::AIOutput;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// USD per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+    /// Price of prompt tokens served from cache; `input` when not set
+    #[serde(rename = "cachedInput", default, skip_serializing_if = "Option::is_none")]
+    pub cached_input: Option<f64>,
+}
+
+const fn price(input: f64, output: f64, cached_input: Option<f64>) -> ModelPrice {
+    ModelPrice { input, output, cached_input }
+}
+
+/// List prices at the time of writing. Dated snapshots (`gpt-4o-2024-08-06`,
+/// `claude-3-5-sonnet-20241022`, `gpt-4-0613`) and `-latest` aliases resolve
+/// to their base model; other variants (`gemini-2.5-flash-lite`) are priced
+/// only if listed themselves.
+const SHIPPED_PRICES: &[(&str, &str, ModelPrice)] = &[
+    ("openai", "gpt-4o", price(2.5, 10.0, Some(1.25))),
+    ("openai", "gpt-4o-mini", price(0.15, 0.6, Some(0.075))),
+    ("openai", "gpt-4.1", price(2.0, 8.0, Some(0.5))),
+    ("openai", "gpt-4.1-mini", price(0.4, 1.6, Some(0.1))),
+    ("openai", "gpt-4.1-nano", price(0.1, 0.4, Some(0.025))),
+    ("openai", "gpt-4-turbo", price(10.0, 30.0, None)),
+    ("openai", "gpt-4", price(30.0, 60.0, None)),
+    ("openai", "gpt-3.5-turbo", price(0.5, 1.5, None)),
+    ("openai", "o1", price(15.0, 60.0, Some(7.5))),
+    ("openai", "o1-mini", price(1.1, 4.4, Some(0.55))),
+    ("openai", "o3-mini", price(1.1, 4.4, Some(0.55))),
+    ("anthropic", "claude-opus-4", price(15.0, 75.0, Some(1.5))),
+    ("anthropic", "claude-sonnet-4", price(3.0, 15.0, Some(0.3))),
+    ("anthropic", "claude-3-7-sonnet", price(3.0, 15.0, Some(0.3))),
+    ("anthropic", "claude-3-5-sonnet", price(3.0, 15.0, Some(0.3))),
+    ("anthropic", "claude-3-5-haiku", price(0.8, 4.0, Some(0.08))),
+    ("anthropic", "claude-3-opus", price(15.0, 75.0, Some(1.5))),
+    ("anthropic", "claude-3-haiku", price(0.25, 1.25, Some(0.03))),
+    ("gemini", "gemini-2.5-pro", price(1.25, 10.0, Some(0.31))),
+    ("gemini", "gemini-2.5-flash", price(0.3, 2.5, Some(0.075))),
+    ("gemini", "gemini-2.0-flash", price(0.1, 0.4, Some(0.025))),
+    ("gemini", "gemini-1.5-pro", price(1.25, 5.0, None)),
+    ("gemini", "gemini-1.5-flash", price(0.075, 0.3, None)),
+];
+
+/// Writes to Anthropic's prompt cache (5-minute TTL) are billed at this
+/// multiple of the input price
+const CACHE_WRITE_MULTIPLIER: f64 = 1.25;
+
+/// Providers that run on the user's own hardware (or not at all)
+const LOCAL_PROVIDERS: &[&str] = &["ollama", "lmstudio", "mock"];
+
+#[derive(Default)]
+pub struct PricingRegistry {
+    overrides: HashMap<String, ModelPrice>,
+}
+
+impl PricingRegistry {
+    pub fn new(overrides: HashMap<String, ModelPrice>) -> Self {
+        Self { overrides }
+    }
+
+    /// Shipped prices plus the user's overrides from the store
+    pub fn load() -> Self {
+        Self::new(crate::load_from_store("evvl_model_pricing").unwrap_or_default())
+    }
+
+    /// Price for a model: user overrides first, then shipped data. Local
+    /// providers are free.
+    pub fn price(&self, provider: &str, model: &str) -> Option<ModelPrice> {
+        let overridden = best_match(
+            self.overrides.iter().filter_map(|(key, price)| {
+                let (p, m) = key.split_once('/')?;
+                (p == provider).then_some((m, *price))
+            }),
+            model,
+        );
+        if overridden.is_some() {
+            return overridden;
+        }
+        if LOCAL_PROVIDERS.contains(&provider) {
+            return Some(price(0.0, 0.0, None));
+        }
+        best_match(
+            SHIPPED_PRICES
+                .iter()
+                .filter(|(p, _, _)| *p == provider)
+                .map(|(_, m, price)| (*m, *price)),
+            model,
+        )
+    }
+
+    /// Estimated cost in USD of a call with the given token counts. `cached`
+    /// and `cache_writes` are parts of `input`.
+    pub fn cost(
+        &self,
+        provider: &str,
+        model: &str,
+        input: Option<i64>,
+        cached: Option<i64>,
+        cache_writes: Option<i64>,
+        output: Option<i64>,
+    ) -> Option<f64> {
+        if input.is_none() && output.is_none() {
+            return None;
+        }
+        let price = self.price(provider, model)?;
+        let cached = cached.unwrap_or(0);
+        let cache_writes = cache_writes.unwrap_or(0);
+        let uncached = input.unwrap_or(0) - cached - cache_writes;
+        let total = uncached as f64 * price.input
+            + cached as f64 * price.cached_input.unwrap_or(price.input)
+            + cache_writes as f64 * price.input * CACHE_WRITE_MULTIPLIER
+            + output.unwrap_or(0) as f64 * price.output;
+        Some(total / 1_000_000.0)
+    }
+
+    /// Cost of a stored output: the billed cost when the provider reported
+    /// one, otherwise an estimate from its token counts
+    pub fn output_cost(&self, output: &AIOutput) -> Option<f64> {
+        output.cost.or_else(|| {
+            self.cost(
+                &output.model_config.provider,
+                &output.model_config.model,
+                output.input_tokens,
+                output.cached_tokens,
+                output.cache_write_tokens,
+                output.output_tokens,
+            )
+        })
+    }
+}
+
+/// Exact name, else the entry the model is a snapshot or `-latest` alias of
+fn best_match<'a>(entries: impl Iterator<Item = (&'a str, ModelPrice)>, model: &str) -> Option<ModelPrice> {
+    entries
+        .filter(|(name, _)| model.strip_prefix(name).is_some_and(|suffix| suffix.is_empty() || is_snapshot(suffix)))
+        .max_by_key(|(name, _)| name.len())
+        .map(|(_, price)| price)
+}
+
+/// `-latest`, or a date stamp: `-2024-08-06`, `-20241022` or `-0613`
+fn is_snapshot(suffix: &str) -> bool {
+    let Some(stamp) = suffix.strip_prefix('-') else {
+        return false;
+    };
+    let digits = |part: &str, len: usize| part.len() == len && part.bytes().all(|b| b.is_ascii_digit());
+    match stamp.split('-').collect::<Vec<_>>()[..] {
+        ["latest"] => true,
+        [date] => digits(date, 8) || digits(date, 4),
+        [year, month, day] => digits(year, 4) && digits(month, 2) && digits(day, 2),
+        _ => false,
+    }
+}
+
+/// Sum of the costs that are known; `None` when none are
+pub fn total_cost(costs: impl Iterator<Item = Option<f64>>) -> Option<f64> {
+    costs.flatten().fold(None, |sum, cost| Some(sum.unwrap_or(0.0) + cost))
+}
+
+/// A run's total for display, marked as a lower bound when `unpriced`
+/// results (billed, but with no known cost) are left out of it
+pub fn describe_total(total: Option<f64>, unpriced: usize) -> Option<String> {
+    let unpriced_note = match unpriced {
+        1 => "1 result unpriced".to_string(),
+        n => format!("{} results unpriced", n),
+    };
+    match (total, unpriced) {
+        (None, 0) => None,
+        (Some(total), 0) => Some(format_cost(total)),
+        (Some(total), _) => Some(format!("≥ {}, {}", format_cost(total), unpriced_note)),
+        (None, _) => Some(format!("unknown, {}", unpriced_note)),
+    }
+}
+
+/// Format a USD amount with enough precision for sub-cent calls
+pub fn format_cost(cost: f64) -> String {
+    if cost >= 0.01 || cost == 0.0 {
+        format!("${:.2}", cost)
+    } else {
+        format!("${:.5}", cost)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price_lookup_resolves_snapshots() {
+        let registry = PricingRegistry::default();
+        assert_eq!(registry.price("openai", "gpt-4o-2024-08-06").unwrap().input, 2.5);
+        // Longest match wins, and `gpt-4` does not swallow `gpt-4o`
+        assert_eq!(registry.price("openai", "gpt-4o-mini").unwrap().input, 0.15);
+        assert_eq!(registry.price("openai", "gpt-4-0613").unwrap().input, 30.0);
+        assert!(registry.price("openai", "gpt-4.5-preview").is_none());
+        assert_eq!(registry.price("anthropic", "claude-3-5-sonnet-20241022").unwrap().output, 15.0);
+        assert_eq!(registry.price("ollama", "llama3").unwrap().output, 0.0);
+        assert!(registry.price("openrouter", "mistral-large").is_none());
+        assert_eq!(registry.price("anthropic", "claude-3-5-sonnet-latest").unwrap().input, 3.0);
+    }
+
+    #[test]
+    fn test_unlisted_variants_stay_unpriced() {
+        let registry = PricingRegistry::default();
+        assert!(registry.price("gemini", "gemini-2.5-flash-lite").is_none());
+        assert!(registry.price("anthropic", "claude-opus-4-5").is_none());
+        assert!(registry.price("anthropic", "claude-sonnet-4-5-20250929").is_none());
+        assert!(registry.price("openai", "gpt-4o-audio-preview").is_none());
+        assert!(registry.price("openai", "o1-preview-2024-09-12").is_none());
+    }
+
+    #[test]
+    fn test_overrides_win() {
+        let mut overrides = HashMap::new();
+        overrides.insert("openai/gpt-4o".to_string(), price(1.0, 2.0, None));
+        overrides.insert("openrouter/mistralai/mistral-large".to_string(), price(2.0, 6.0, None));
+        let registry = PricingRegistry::new(overrides);
+
+        assert_eq!(registry.price("openai", "gpt-4o").unwrap().input, 1.0);
+        assert_eq!(registry.price("openrouter", "mistralai/mistral-large").unwrap().output, 6.0);
+    }
+
+    #[test]
+    fn test_cost_with_cached_tokens() {
+        let registry = PricingRegistry::default();
+        // 1M prompt tokens of which 400k cached, 100k output on gpt-4o
+        let cost = registry
+            .cost("openai", "gpt-4o", Some(1_000_000), Some(400_000), None, Some(100_000))
+            .unwrap();
+        assert!((cost - (0.6 * 2.5 + 0.4 * 1.25 + 0.1 * 10.0)).abs() < 1e-9);
+        assert_eq!(registry.cost("openai", "gpt-4o", None, None, None, None), None);
+    }
+
+    #[test]
+    fn test_cache_writes_cost_more_than_input() {
+        let registry = PricingRegistry::default();
+        // 1M prompt tokens: 500k written to the cache, 200k read from it
+        let cost = registry
+            .cost("anthropic", "claude-sonnet-4", Some(1_000_000), Some(200_000), Some(500_000), None)
+            .unwrap();
+        assert!((cost - (0.3 * 3.0 + 0.2 * 0.3 + 0.5 * 3.75)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_total_and_format() {
+        assert_eq!(total_cost([Some(0.5), None, Some(0.25)].into_iter()), Some(0.75));
+        assert_eq!(total_cost([None, None].into_iter()), None);
+        assert_eq!(describe_total(Some(0.75), 0).as_deref(), Some("$0.75"));
+        assert_eq!(describe_total(Some(0.75), 2).as_deref(), Some("≥ $0.75, 2 results unpriced"));
+        assert_eq!(describe_total(None, 1).as_deref(), Some("unknown, 1 result unpriced"));
+        assert_eq!(describe_total(None, 0), None);
+        assert_eq!(format_cost(1.5), "$1.50");
+        assert_eq!(format_cost(0.00042), "$0.00042");
+    }
+}
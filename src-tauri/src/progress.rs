@@ -0,0 +1,199 @@
+//! Feedback while a headless run executes: a live per-model view on the
+//! terminal, or NDJSON events for `--json`.
+
+use crate::runner::{self, RunEvent, RunPlan};
+use crate::EvaluationRun;
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+const LABEL_WIDTH: usize = 32;
+
+/// Print one event as a single JSON line on stdout
+pub fn print_event(event: RunEvent) {
+    let line = serde_json::to_string(&event).unwrap();
+    let mut stdout = std::io::stdout().lock();
+    let _ = writeln!(stdout, "{}", line);
+    let _ = stdout.flush();
+}
+
+/// One line of the live view
+struct Row {
+    model_config_id: String,
+    label: String,
+    total: usize,
+    finished: usize,
+    failed: usize,
+    /// Finished cells served from the response cache
+    cached: usize,
+    /// Tokens reported by finished cells
+    tokens: i64,
+    /// Chunks received so far by each cell in flight (keyed by dataset
+    /// item), shown as an estimate
+    in_flight: HashMap<Option<String>, i64>,
+    started: Option<Instant>,
+    ended: Option<Instant>,
+}
+
+impl Row {
+    fn render(&self) -> String {
+        let status = if self.finished == self.total {
+            if self.failed == 0 { "done" } else { "failed" }
+        } else if self.in_flight.values().any(|&chunks| chunks > 0) {
+            "streaming"
+        } else if !self.in_flight.is_empty() {
+            "running"
+        } else if self.started.is_some() {
+            "queued"
+        } else {
+            "waiting"
+        };
+        let elapsed = match (self.started, self.ended) {
+            (Some(started), Some(ended)) => ended - started,
+            (Some(started), None) => started.elapsed(),
+            _ => Duration::ZERO,
+        };
+        let streamed: i64 = self.in_flight.values().sum();
+        let tokens = if streamed > 0 {
+            format!("~{}", self.tokens + streamed)
+        } else {
+            self.tokens.to_string()
+        };
+        let mut line = format!(
+            "  {:<width$} {:<9} {:>6.1}s {:>8} tokens  [{}/{}]",
+            crate::truncate_string(&self.label, LABEL_WIDTH),
+            status,
+            elapsed.as_secs_f64(),
+            tokens,
+            self.finished,
+            self.total,
+            width = LABEL_WIDTH,
+        );
+        if self.cached > 0 {
+            line.push_str(&format!(" {} cached", self.cached));
+        }
+        if self.failed > 0 {
+            line.push_str(&format!(" {} error(s)", self.failed));
+        }
+        line
+    }
+}
+
+/// Multi-line status view on stderr, one row per model config, redrawn in
+/// place while the run executes
+pub struct LiveView {
+    rows: Mutex<Vec<Row>>,
+    drawn: Mutex<usize>,
+}
+
+impl LiveView {
+    pub fn new(plan: &RunPlan) -> Self {
+        let rows = plan
+            .model_configs
+            .iter()
+            .map(|config| Row {
+                model_config_id: config.id.clone(),
+                label: format!("{}/{}", config.provider, config.model),
+                total: plan.items.len().max(1),
+                finished: 0,
+                failed: 0,
+                cached: 0,
+                tokens: 0,
+                in_flight: HashMap::new(),
+                started: None,
+                ended: None,
+            })
+            .collect();
+        Self {
+            rows: Mutex::new(rows),
+            drawn: Mutex::new(0),
+        }
+    }
+
+    /// View of a resumed run: one row per model with cells left to execute
+    pub fn resuming(plan: &RunPlan, run: &EvaluationRun) -> Self {
+        let view = Self::new(plan);
+        let pending = runner::pending_cells(plan, run);
+        view.rows.lock().unwrap().retain_mut(|row| {
+            row.total = pending.iter().filter(|(config, _)| config.id == row.model_config_id).count();
+            row.total > 0
+        });
+        view
+    }
+
+    pub fn handle(&self, event: &RunEvent) {
+        let mut rows = self.rows.lock().unwrap();
+        let (id, item_id) = match event {
+            RunEvent::Start { model_config_id, data_set_item_id, .. }
+            | RunEvent::Delta { model_config_id, data_set_item_id, .. }
+            | RunEvent::Done { model_config_id, data_set_item_id, .. }
+            | RunEvent::Error { model_config_id, data_set_item_id, .. } => (model_config_id, data_set_item_id),
+        };
+        let Some(row) = rows.iter_mut().find(|r| &r.model_config_id == id) else {
+            return;
+        };
+
+        match event {
+            RunEvent::Start { .. } => {
+                row.started.get_or_insert_with(Instant::now);
+                row.in_flight.insert(item_id.clone(), 0);
+            }
+            RunEvent::Delta { .. } => {
+                *row.in_flight.entry(item_id.clone()).or_insert(0) += 1;
+            }
+            RunEvent::Done { tokens, cached, .. } => {
+                let streamed = row.in_flight.remove(item_id).unwrap_or(0);
+                row.tokens += tokens.unwrap_or(streamed);
+                row.finished += 1;
+                if *cached {
+                    row.cached += 1;
+                }
+            }
+            RunEvent::Error { .. } => {
+                row.in_flight.remove(item_id);
+                row.failed += 1;
+                row.finished += 1;
+            }
+        }
+        if row.finished == row.total {
+            row.ended.get_or_insert_with(Instant::now);
+        }
+    }
+
+    /// Redraw every row, moving the cursor back over the previous frame
+    pub fn draw(&self) {
+        let rows = self.rows.lock().unwrap();
+        let mut drawn = self.drawn.lock().unwrap();
+        let mut stderr = std::io::stderr().lock();
+
+        if *drawn > 0 {
+            let _ = write!(stderr, "\x1b[{}A", *drawn);
+        }
+        for row in rows.iter() {
+            let _ = writeln!(stderr, "\r\x1b[2K{}", row.render());
+        }
+        let _ = stderr.flush();
+        *drawn = rows.len();
+    }
+
+    /// Run `work` while a background thread keeps the view up to date
+    pub fn run<T>(&self, work: impl FnOnce() -> T) -> T {
+        let finished = AtomicBool::new(false);
+        let output = std::thread::scope(|scope| {
+            scope.spawn(|| {
+                while !finished.load(Ordering::Relaxed) {
+                    self.draw();
+                    std::thread::sleep(REDRAW_INTERVAL);
+                }
+            });
+            let output = work();
+            finished.store(true, Ordering::Relaxed);
+            output
+        });
+        self.draw();
+        output
+    }
+}
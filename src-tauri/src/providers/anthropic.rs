@@ -0,0 +1,336 @@
+//! Anthropic Messages API (`/v1/messages`).
+
+use super::{
+    insert_param, read_sse, send, send_json, Completion, Provider, ProviderError, ProviderRequest,
+    TokenUsage,
+};
+use crate::tools::{parse_arguments, ToolCall};
+use serde_json::{json, Map, Value};
+use std::time::Duration;
+
+pub const ANTHROPIC_BASE_URL: &str = "https://api.anthropic.com/v1";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+/// `max_tokens` is mandatory for Anthropic; same default as the web app
+pub const DEFAULT_MAX_TOKENS: u32 = 4096;
+/// Models the API only knows dated or as `<name>-latest`; the bare name
+/// (as in `--models claude-3-5-sonnet`) is sent as the `-latest` alias
+const LATEST_ALIASES: &[&str] = &["claude-3-5-sonnet", "claude-3-5-haiku", "claude-3-7-sonnet", "claude-3-opus"];
+
+pub struct AnthropicProvider {
+    base_url: String,
+    api_key: String,
+}
+
+impl AnthropicProvider {
+    pub fn with_base_url(base_url: &str, api_key: String) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+        }
+    }
+
+    fn body(&self, request: &ProviderRequest) -> Value {
+        let params = request.parameters.clone().unwrap_or_default();
+
+        let mut body = Map::new();
+        body.insert("model".to_string(), json!(api_model(&request.model)));
+        body.insert("max_tokens".to_string(), json!(params.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS)));
+        let (system, turns) = request.conversation();
+        let messages: Vec<Value> = turns
+            .iter()
+            .map(|turn| json!({ "role": turn.role, "content": turn.content }))
+            .collect();
+        body.insert("messages".to_string(), json!(messages));
+        // The system prompt is a top-level field, not a message
+        insert_param(&mut body, "system", &system);
+        insert_param(&mut body, "temperature", &params.temperature);
+        insert_param(&mut body, "top_p", &params.top_p);
+        insert_param(&mut body, "stop_sequences", &params.stop);
+        if !request.tools.is_empty() {
+            let tools: Vec<Value> = request
+                .tools
+                .iter()
+                .map(|tool| {
+                    // Anthropic requires a schema, even for a function without arguments
+                    let schema = match &tool.parameters {
+                        Value::Null => json!({ "type": "object", "properties": {} }),
+                        schema => schema.clone(),
+                    };
+                    let mut definition = json!({ "name": tool.name, "input_schema": schema });
+                    if let Some(description) = &tool.description {
+                        definition["description"] = json!(description);
+                    }
+                    definition
+                })
+                .collect();
+            body.insert("tools".to_string(), json!(tools));
+        }
+        Value::Object(body)
+    }
+
+    fn http(&self, timeout: Duration) -> ureq::Request {
+        ureq::post(&format!("{}/messages", self.base_url))
+            .timeout(timeout)
+            .set("x-api-key", &self.api_key)
+            .set("anthropic-version", ANTHROPIC_VERSION)
+    }
+}
+
+fn api_model(model: &str) -> String {
+    if LATEST_ALIASES.contains(&model) {
+        format!("{}-latest", model)
+    } else {
+        model.to_string()
+    }
+}
+
+/// Anthropic reports cache reads and writes separately from `input_tokens`;
+/// fold them back in so `input` is the whole prompt, like the other providers
+fn usage_from(usage: &Value) -> TokenUsage {
+    let cached = usage["cache_read_input_tokens"].as_i64();
+    let cache_writes = usage["cache_creation_input_tokens"].as_i64();
+    TokenUsage {
+        input: usage["input_tokens"]
+            .as_i64()
+            .map(|input| input + cached.unwrap_or(0) + cache_writes.unwrap_or(0)),
+        output: usage["output_tokens"].as_i64(),
+        cached,
+        cache_writes,
+        reasoning: None,
+    }
+}
+
+/// Map an `error` event sent mid-stream onto the status the same failure
+/// would have had before the stream started
+fn stream_error(error: &Value) -> ProviderError {
+    let status = match error["type"].as_str() {
+        Some("overloaded_error") => 529,
+        Some("rate_limit_error") => 429,
+        Some("api_error") => 500,
+        _ => 400,
+    };
+    ProviderError::Http {
+        status,
+        message: error["message"].as_str().unwrap_or("stream error").to_string(),
+        retry_after: None,
+    }
+}
+
+impl Provider for AnthropicProvider {
+    fn name(&self) -> &'static str {
+        "Anthropic"
+    }
+
+    fn complete(&self, request: &ProviderRequest) -> Result<Completion, ProviderError> {
+        let data = send_json(self.http(request.call_timeout()), &self.body(request))?;
+
+        let blocks = data["content"]
+            .as_array()
+            .ok_or_else(|| ProviderError::InvalidResponse("missing content blocks".to_string()))?;
+        let content: String = blocks
+            .iter()
+            .filter(|block| block["type"] == "text")
+            .filter_map(|block| block["text"].as_str())
+            .collect();
+        let tool_calls = blocks
+            .iter()
+            .filter(|block| block["type"] == "tool_use")
+            .map(|block| ToolCall {
+                id: block["id"].as_str().map(|s| s.to_string()),
+                name: block["name"].as_str().unwrap_or_default().to_string(),
+                arguments: block["input"].clone(),
+            })
+            .collect();
+
+        Ok(Completion {
+            content,
+            usage: usage_from(&data["usage"]),
+            finish_reason: data["stop_reason"].as_str().map(|s| s.to_string()),
+            tool_calls,
+            ..Default::default()
+        })
+    }
+
+    fn stream(
+        &self,
+        request: &ProviderRequest,
+        on_delta: &mut dyn FnMut(&str),
+    ) -> Result<Completion, ProviderError> {
+        let mut body = self.body(request);
+        body["stream"] = json!(true);
+        let response = send(self.http(request.call_timeout()), &body)?;
+
+        let mut completion = Completion::default();
+        // `tool_use` blocks by content index, with their input JSON so far
+        let mut calls: Vec<(u64, ToolCall, String)> = Vec::new();
+        read_sse(response, request.cancel.as_ref(), |event, data| {
+            match event {
+                Some("message_start") => {
+                    completion.usage = usage_from(&data["message"]["usage"]);
+                }
+                Some("content_block_start") if data["content_block"]["type"] == "tool_use" => {
+                    let block = &data["content_block"];
+                    let call = ToolCall {
+                        id: block["id"].as_str().map(|s| s.to_string()),
+                        name: block["name"].as_str().unwrap_or_default().to_string(),
+                        arguments: Value::Null,
+                    };
+                    calls.push((data["index"].as_u64().unwrap_or(0), call, String::new()));
+                }
+                Some("content_block_delta") => {
+                    if let Some(text) = data["delta"]["text"].as_str() {
+                        completion.content.push_str(text);
+                        on_delta(text);
+                    }
+                    if let Some(fragment) = data["delta"]["partial_json"].as_str() {
+                        let index = data["index"].as_u64().unwrap_or(0);
+                        if let Some((_, _, input)) = calls.iter_mut().find(|(i, _, _)| *i == index) {
+                            input.push_str(fragment);
+                        }
+                    }
+                }
+                // Carries the cumulative output token count
+                Some("message_delta") => {
+                    if let Some(output) = data["usage"]["output_tokens"].as_i64() {
+                        completion.usage.output = Some(output);
+                    }
+                    if let Some(reason) = data["delta"]["stop_reason"].as_str() {
+                        completion.finish_reason = Some(reason.to_string());
+                    }
+                }
+                Some("error") => return Err(stream_error(&data["error"])),
+                _ => {}
+            }
+            Ok(())
+        })?;
+        completion.tool_calls = calls
+            .into_iter()
+            .map(|(_, call, input)| ToolCall { arguments: parse_arguments(&input), ..call })
+            .collect();
+        Ok(completion)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parameters::AIParameters;
+    use crate::providers::test_server;
+
+    #[test]
+    fn test_messages_against_local_server() {
+        let server = test_server::respond(
+            200,
+            r#"{"content":[{"type":"text","text":"Hello"},{"type":"text","text":" there"}],
+                "usage":{"input_tokens":10,"cache_creation_input_tokens":6,"output_tokens":3}}"#,
+        );
+        let provider = AnthropicProvider::with_base_url(&server.base_url("/v1"), "sk-ant".to_string());
+        let request = ProviderRequest {
+            model: "claude-3-5-sonnet-20241022".to_string(),
+            prompt: "Greet me".to_string(),
+            system_prompt: Some("You are terse".to_string()),
+            parameters: Some(AIParameters {
+                max_tokens: Some(256),
+                stop: Some(vec!["END".to_string()]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let completion = provider.complete(&request).unwrap();
+        assert_eq!(completion.content, "Hello there");
+        assert_eq!(completion.usage.input, Some(16));
+        assert_eq!(completion.usage.cache_writes, Some(6));
+        assert_eq!(completion.usage.output, Some(3));
+        assert_eq!(completion.usage.total(), Some(19));
+
+        let captured = server.request();
+        assert_eq!(captured.path, "/v1/messages");
+        assert_eq!(captured.header("x-api-key"), Some("sk-ant"));
+        assert_eq!(captured.body["system"], "You are terse");
+        assert_eq!(captured.body["messages"].as_array().unwrap().len(), 1);
+        assert_eq!(captured.body["max_tokens"], 256);
+        assert_eq!(captured.body["stop_sequences"][0], "END");
+    }
+
+    #[test]
+    fn test_bare_model_name_sent_as_latest_alias() {
+        assert_eq!(api_model("claude-3-5-sonnet"), "claude-3-5-sonnet-latest");
+        assert_eq!(api_model("claude-3-5-sonnet-20241022"), "claude-3-5-sonnet-20241022");
+        assert_eq!(api_model("claude-sonnet-4-5"), "claude-sonnet-4-5");
+    }
+
+    #[test]
+    fn test_streaming_messages() {
+        let server = test_server::serve(vec![test_server::CannedResponse {
+            status: 200,
+            headers: vec![("Content-Type".to_string(), "text/event-stream".to_string())],
+            body: [
+                "event: message_start\ndata: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":12,\"cache_read_input_tokens\":30,\"output_tokens\":1}}}",
+                "event: ping\ndata: {\"type\":\"ping\"}",
+                "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"delta\":{\"type\":\"text_delta\",\"text\":\"Hi\"}}",
+                "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"delta\":{\"type\":\"text_delta\",\"text\":\" there\"}}",
+                "event: message_delta\ndata: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"max_tokens\"},\"usage\":{\"output_tokens\":5}}",
+                "event: message_stop\ndata: {\"type\":\"message_stop\"}",
+            ]
+            .join("\n\n"),
+        }]);
+        let provider = AnthropicProvider::with_base_url(&server.base_url("/v1"), "sk-ant".to_string());
+        let request = ProviderRequest {
+            model: "claude-3-5-sonnet-20241022".to_string(),
+            prompt: "Greet me".to_string(),
+            ..Default::default()
+        };
+
+        let mut chunks = 0;
+        let completion = provider.stream(&request, &mut |_| chunks += 1).unwrap();
+        assert_eq!(chunks, 2);
+        assert_eq!(completion.content, "Hi there");
+        assert_eq!(completion.usage.input, Some(42));
+        assert_eq!(completion.usage.cached, Some(30));
+        assert_eq!(completion.usage.output, Some(5));
+        assert_eq!(completion.finish_reason.as_deref(), Some("max_tokens"));
+        assert_eq!(server.request().body["stream"], true);
+    }
+
+    #[test]
+    fn test_streamed_tool_use_block() {
+        let server = test_server::serve(vec![test_server::CannedResponse {
+            status: 200,
+            headers: vec![("Content-Type".to_string(), "text/event-stream".to_string())],
+            body: [
+                "event: content_block_start\ndata: {\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}",
+                "event: content_block_delta\ndata: {\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Checking\"}}",
+                "event: content_block_start\ndata: {\"index\":1,\"content_block\":{\"type\":\"tool_use\",\"id\":\"toolu_1\",\"name\":\"get_weather\",\"input\":{}}}",
+                "event: content_block_delta\ndata: {\"index\":1,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"{\\\"city\\\": \"}}",
+                "event: content_block_delta\ndata: {\"index\":1,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"\\\"Paris\\\"}\"}}",
+                "event: message_delta\ndata: {\"delta\":{\"stop_reason\":\"tool_use\"},\"usage\":{\"output_tokens\":9}}",
+            ]
+            .join("\n\n"),
+        }]);
+        let provider = AnthropicProvider::with_base_url(&server.base_url("/v1"), "sk-ant".to_string());
+        let request = ProviderRequest {
+            model: "claude-3-5-sonnet-20241022".to_string(),
+            prompt: "Weather in Paris?".to_string(),
+            tools: vec![crate::tools::ToolDefinition {
+                name: "get_weather".to_string(),
+                description: None,
+                parameters: Value::Null,
+            }],
+            ..Default::default()
+        };
+
+        let completion = provider.stream(&request, &mut |_| {}).unwrap();
+        assert_eq!(completion.content, "Checking");
+        assert_eq!(completion.tool_calls.len(), 1);
+        assert_eq!(completion.tool_calls[0].id.as_deref(), Some("toolu_1"));
+        assert_eq!(completion.tool_calls[0].arguments, json!({ "city": "Paris" }));
+        assert_eq!(server.request().body["tools"][0]["input_schema"]["type"], "object");
+    }
+
+    #[test]
+    fn test_stream_error_event_maps_status() {
+        let error = stream_error(&json!({ "type": "overloaded_error", "message": "Overloaded" }));
+        assert_eq!(error.details().status, Some(529));
+    }
+}
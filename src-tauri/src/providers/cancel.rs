@@ -0,0 +1,197 @@
+//! Cancellation of in-flight provider calls (Ctrl-C in the CLI, the cancel
+//! button in the GUI).
+//!
+//! A cancellable call runs on its own thread with the token attached to its
+//! request, and the caller stops waiting for it as soon as the token is
+//! cancelled. The call itself stops too: it is always streamed, and the
+//! stream readers check the token on every line, so the call returns and
+//! drops its response, closing the connection, which makes the provider
+//! stop generating. Retry waits and mock latency end early the same way.
+//!
+//! ureq has no way to interrupt a blocked read, so a call still waiting for
+//! its first byte closes its connection when that byte arrives (or when the
+//! attempt times out) rather than at once. Output generated until then may
+//! still be billed.
+
+use super::{error_response, generate, ProviderError, ProviderResponse, ProviderRequest, RetryPolicy};
+use crate::ApiKeys;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How often a waiting caller looks at the token
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Shared flag telling every call holding a clone to stop
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Sleep for `duration`, or less if `cancel` is cancelled meanwhile
+pub(crate) fn pause(duration: Duration, cancel: Option<&CancelToken>) -> Result<(), ProviderError> {
+    let Some(cancel) = cancel else {
+        std::thread::sleep(duration);
+        return Ok(());
+    };
+    let until = Instant::now() + duration;
+    loop {
+        if cancel.is_cancelled() {
+            return Err(ProviderError::Cancelled);
+        }
+        let left = until.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Ok(());
+        }
+        std::thread::sleep(left.min(POLL_INTERVAL));
+    }
+}
+
+/// `Err(Cancelled)` once `cancel` is cancelled
+pub(crate) fn check(cancel: Option<&CancelToken>) -> Result<(), ProviderError> {
+    match cancel {
+        Some(cancel) if cancel.is_cancelled() => Err(ProviderError::Cancelled),
+        _ => Ok(()),
+    }
+}
+
+enum Message {
+    Delta(String),
+    Done(Box<ProviderResponse>),
+}
+
+/// `generate`, stopped once `cancel` is cancelled. A cancelled call comes
+/// back at once as an error of kind `cancelled`; its request is streamed
+/// even without `on_delta`, so its thread can close the connection at the
+/// next chunk (see the module docs).
+pub fn generate_cancellable(
+    provider: &str,
+    keys: &ApiKeys,
+    request: &ProviderRequest,
+    retry: &RetryPolicy,
+    cancel: &CancelToken,
+    mut on_delta: Option<&mut dyn FnMut(&str)>,
+) -> ProviderResponse {
+    let started = Instant::now();
+    if cancel.is_cancelled() {
+        return cancelled(started);
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let streaming = on_delta.is_some();
+    let (provider, keys, retry) = (provider.to_string(), keys.clone(), retry.clone());
+    let request = ProviderRequest { cancel: Some(cancel.clone()), ..request.clone() };
+    std::thread::spawn(move || {
+        let delta_sender = sender.clone();
+        let mut forward = |text: &str| {
+            if streaming {
+                let _ = delta_sender.send(Message::Delta(text.to_string()));
+            }
+        };
+        let response = generate(&provider, &keys, &request, &retry, Some(&mut forward));
+        let _ = sender.send(Message::Done(Box::new(response)));
+    });
+
+    loop {
+        if cancel.is_cancelled() {
+            return cancelled(started);
+        }
+        match receiver.recv_timeout(POLL_INTERVAL) {
+            Ok(Message::Delta(text)) => {
+                if let Some(on_delta) = on_delta.as_mut() {
+                    on_delta(&text);
+                }
+            }
+            // The call noticed the token before we did
+            Ok(Message::Done(_)) if cancel.is_cancelled() => return cancelled(started),
+            Ok(Message::Done(response)) => return *response,
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                let e = ProviderError::Transport("the request thread stopped unexpectedly".to_string());
+                return error_response(e.to_string(), e.details(), started.elapsed().as_millis() as i64, 1);
+            }
+        }
+    }
+}
+
+fn cancelled(started: Instant) -> ProviderResponse {
+    let e = ProviderError::Cancelled;
+    error_response(e.to_string(), e.details(), started.elapsed().as_millis() as i64, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(model: &str) -> ProviderRequest {
+        ProviderRequest {
+            model: model.to_string(),
+            prompt: "Hello there".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_cancel_returns_at_once() {
+        let cancel = CancelToken::default();
+        let canceller = cancel.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            canceller.cancel();
+        });
+
+        let started = Instant::now();
+        let response = generate_cancellable(
+            "mock",
+            &ApiKeys::default(),
+            &request("echo?latency=5000"),
+            &RetryPolicy::default(),
+            &cancel,
+            None,
+        );
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(response.error.as_deref(), Some("Request cancelled"));
+        assert_eq!(response.error_details.unwrap().kind, "cancelled");
+    }
+
+    #[test]
+    fn test_cancel_stops_the_call_itself() {
+        let cancel = CancelToken::default();
+        let canceller = cancel.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            canceller.cancel();
+        });
+
+        // No waiting caller here: the call has to notice the token on its own
+        let started = Instant::now();
+        let request = ProviderRequest { cancel: Some(cancel), ..request("echo?latency=5000") };
+        let response = generate("mock", &ApiKeys::default(), &request, &RetryPolicy::default(), None);
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(response.error_details.unwrap().kind, "cancelled");
+    }
+
+    #[test]
+    fn test_uncancelled_call_streams_through() {
+        let mut chunks = Vec::new();
+        let response = generate_cancellable(
+            "mock",
+            &ApiKeys::default(),
+            &request("echo"),
+            &RetryPolicy::default(),
+            &CancelToken::default(),
+            Some(&mut |text| chunks.push(text.to_string())),
+        );
+        assert!(response.error.is_none());
+        assert_eq!(chunks.concat(), "Hello there");
+    }
+}
@@ -0,0 +1,332 @@
+//! Record/replay of raw provider HTTP exchanges, for offline and
+//! deterministic runs.
+//!
+//! `--record <file>` performs every call for real and writes the exchanges to
+//! a JSON cassette; `--replay <file>` answers every call from the cassette
+//! without touching the network. Requests are matched on method, URL and
+//! body (less the `stream` switches), each recorded exchange being served
+//! once, in recording order. Calls are replayed streaming or not as they
+//! were recorded, whatever the replaying run asks for, since that decides
+//! the shape of the recorded body. Request headers (API keys) are never
+//! written.
+
+use super::{status_error, transport_error, ProviderError};
+use crate::ApiKeys;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+const CASSETTE_VERSION: u32 = 1;
+/// Response headers the providers look at; everything else is left out
+const KEPT_HEADERS: &[&str] = &["content-type", "retry-after"];
+/// Body keys that only say whether to stream, ignored when matching
+const STREAM_KEYS: &[&str] = &["stream", "stream_options"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct RecordedRequest {
+    method: String,
+    url: String,
+    body: Value,
+}
+
+impl RecordedRequest {
+    fn matches(&self, other: &RecordedRequest) -> bool {
+        self.method == other.method && self.url == other.url && without_stream(&self.body) == without_stream(&other.body)
+    }
+
+    fn streams(&self) -> bool {
+        self.body["stream"] == true
+    }
+}
+
+fn without_stream(body: &Value) -> Value {
+    let mut body = body.clone();
+    if let Value::Object(fields) = &mut body {
+        for key in STREAM_KEYS {
+            fields.remove(*key);
+        }
+    }
+    body
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordedResponse {
+    status: u16,
+    #[serde(default)]
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Interaction {
+    request: RecordedRequest,
+    response: RecordedResponse,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CassetteFile {
+    version: u32,
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Record,
+    Replay,
+}
+
+pub struct Cassette {
+    path: PathBuf,
+    mode: Mode,
+    /// Exchanges with a flag telling whether replay already served them
+    interactions: Mutex<Vec<(Interaction, bool)>>,
+}
+
+static ACTIVE: OnceLock<Cassette> = OnceLock::new();
+
+/// Route every provider call of this process through `cassette`
+pub fn install(cassette: Cassette) {
+    let _ = ACTIVE.set(cassette);
+}
+
+pub(super) fn active() -> Option<&'static Cassette> {
+    ACTIVE.get()
+}
+
+/// Whether calls are being answered from a cassette
+pub(super) fn replaying() -> bool {
+    active().is_some_and(|cassette| cassette.mode == Mode::Replay)
+}
+
+/// Write the installed cassette to disk when recording
+pub fn save_active() -> Result<(), String> {
+    match active() {
+        Some(cassette) if cassette.mode == Mode::Record => cassette.save(),
+        _ => Ok(()),
+    }
+}
+
+impl Cassette {
+    pub fn record(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            mode: Mode::Record,
+            interactions: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn replay(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("cannot read cassette {}: {}", path.display(), e))?;
+        let file: CassetteFile = serde_json::from_str(&content)
+            .map_err(|e| format!("cassette {} is not valid: {}", path.display(), e))?;
+        if file.version != CASSETTE_VERSION {
+            return Err(format!(
+                "cassette {} has version {}, expected {}",
+                path.display(),
+                file.version,
+                CASSETTE_VERSION
+            ));
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            mode: Mode::Replay,
+            interactions: Mutex::new(file.interactions.into_iter().map(|i| (i, false)).collect()),
+        })
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let file = CassetteFile {
+            version: CASSETTE_VERSION,
+            interactions: self.interactions.lock().unwrap().iter().map(|(i, _)| i.clone()).collect(),
+        };
+        let content = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+        std::fs::write(&self.path, content)
+            .map_err(|e| format!("cannot write cassette {}: {}", self.path.display(), e))
+    }
+
+    /// Whether the recorded calls streamed. Replayed calls have to be made
+    /// the same way for the recorded bodies to parse.
+    pub(super) fn recorded_streaming(&self) -> bool {
+        self.interactions.lock().unwrap().iter().any(|(interaction, _)| interaction.request.streams())
+    }
+
+    /// Stand-in for the real API keys while replaying, so no key is needed
+    pub(super) fn keys(&self, keys: &ApiKeys) -> ApiKeys {
+        if self.mode == Mode::Record {
+            return keys.clone();
+        }
+        let or_placeholder = |key: &Option<String>| Some(key.clone().unwrap_or_else(|| "replay".to_string()));
+        ApiKeys {
+            openai: or_placeholder(&keys.openai),
+            anthropic: or_placeholder(&keys.anthropic),
+            openrouter: or_placeholder(&keys.openrouter),
+            gemini: or_placeholder(&keys.gemini),
+        }
+    }
+
+    /// Send `body` with `request` (recording) or answer it from the
+    /// cassette (replaying)
+    pub(super) fn exchange(&self, request: ureq::Request, body: &Value) -> Result<ureq::Response, ProviderError> {
+        let recorded_request = RecordedRequest {
+            method: request.method().to_string(),
+            url: request.url().to_string(),
+            body: body.clone(),
+        };
+        let response = match self.mode {
+            Mode::Replay => self.find(&recorded_request)?,
+            Mode::Record => {
+                let response = match request.send_json(body.clone()) {
+                    Ok(response) => response,
+                    Err(ureq::Error::Status(_, response)) => response,
+                    Err(e) => return Err(transport_error(&e)),
+                };
+                // Streams are read to the end here, so output shows up only
+                // once the whole response has been recorded
+                let recorded = RecordedResponse {
+                    status: response.status(),
+                    headers: KEPT_HEADERS
+                        .iter()
+                        .filter_map(|name| response.header(name).map(|v| (name.to_string(), v.to_string())))
+                        .collect(),
+                    body: response.into_string().map_err(|e| transport_error(&e))?,
+                };
+                self.interactions.lock().unwrap().push((
+                    Interaction { request: recorded_request, response: recorded.clone() },
+                    true,
+                ));
+                recorded
+            }
+        };
+
+        let status = response.status;
+        let response = to_response(&response)?;
+        if status >= 400 {
+            return Err(status_error(status, response));
+        }
+        Ok(response)
+    }
+
+    /// First recorded exchange for `request` that hasn't been served yet
+    fn find(&self, request: &RecordedRequest) -> Result<RecordedResponse, ProviderError> {
+        let mut interactions = self.interactions.lock().unwrap();
+        let (interaction, served) = interactions
+            .iter_mut()
+            .find(|(interaction, served)| !served && interaction.request.matches(request))
+            .ok_or_else(|| ProviderError::NotRecorded(format!("{} {}", request.method, request.url)))?;
+        *served = true;
+        Ok(interaction.response.clone())
+    }
+}
+
+fn to_response(recorded: &RecordedResponse) -> Result<ureq::Response, ProviderError> {
+    let mut raw = format!("HTTP/1.1 {} Recorded\r\n", recorded.status);
+    for (name, value) in &recorded.headers {
+        raw.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    raw.push_str("\r\n");
+    raw.push_str(&recorded.body);
+    raw.parse::<ureq::Response>()
+        .map_err(|e| ProviderError::InvalidResponse(format!("bad recorded response: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::test_server::{self, CannedResponse};
+    use serde_json::json;
+
+    fn temp_path() -> PathBuf {
+        std::env::temp_dir().join(format!("evvl-cassette-{}.json", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_record_then_replay_offline() {
+        let server = test_server::serve(vec![
+            CannedResponse {
+                status: 429,
+                headers: vec![("Retry-After".to_string(), "2".to_string())],
+                body: r#"{"error": {"message": "slow down"}}"#.to_string(),
+            },
+            CannedResponse {
+                status: 200,
+                headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+                body: r#"{"answer": 42}"#.to_string(),
+            },
+        ]);
+        let url = server.base_url("/v1/chat/completions");
+        let body = json!({ "model": "gpt-4o", "prompt": "Hi" });
+        let path = temp_path();
+
+        let recorder = Cassette::record(&path);
+        let request = || ureq::post(&url).set("Authorization", "Bearer sk-secret");
+        assert!(matches!(recorder.exchange(request(), &body), Err(ProviderError::Http { status: 429, .. })));
+        let response = recorder.exchange(request(), &body).unwrap();
+        assert_eq!(response.into_string().unwrap(), r#"{"answer": 42}"#);
+        recorder.save().unwrap();
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("sk-secret"));
+
+        // The server is gone; the cassette answers in recording order
+        drop(server);
+        let player = Cassette::replay(&path).unwrap();
+        assert!(!player.recorded_streaming());
+        match player.exchange(request(), &body) {
+            Err(ProviderError::Http { status, message, retry_after }) => {
+                assert_eq!(status, 429);
+                assert_eq!(message, "slow down");
+                assert_eq!(retry_after, Some(std::time::Duration::from_secs(2)));
+            }
+            other => panic!("expected the recorded 429, got {:?}", other.map(|r| r.status())),
+        }
+        let response = player.exchange(request(), &body).unwrap();
+        assert_eq!(response.header("content-type"), Some("application/json"));
+        assert_eq!(response.into_json::<Value>().unwrap()["answer"], 42);
+
+        // Everything has been served, and other bodies were never recorded
+        assert!(matches!(player.exchange(request(), &body), Err(ProviderError::NotRecorded(_))));
+        let other = json!({ "model": "gpt-4o", "prompt": "Bye" });
+        assert!(matches!(player.exchange(request(), &other), Err(ProviderError::NotRecorded(_))));
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_streamed_recording_replays_without_streaming() {
+        let server = test_server::respond(200, "data: {\"answer\": 42}\n\ndata: [DONE]\n\n");
+        let url = server.base_url("/v1/chat/completions");
+        let path = temp_path();
+
+        let recorder = Cassette::record(&path);
+        let streamed = json!({
+            "model": "gpt-4o",
+            "prompt": "Hi",
+            "stream": true,
+            "stream_options": { "include_usage": true }
+        });
+        recorder.exchange(ureq::post(&url), &streamed).unwrap();
+        recorder.save().unwrap();
+        drop(server);
+
+        let player = Cassette::replay(&path).unwrap();
+        assert!(player.recorded_streaming());
+        let response = player.exchange(ureq::post(&url), &json!({ "model": "gpt-4o", "prompt": "Hi" })).unwrap();
+        assert!(response.into_string().unwrap().starts_with("data: "));
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_replay_fills_in_missing_keys() {
+        let path = temp_path();
+        std::fs::write(&path, r#"{"version": 1, "interactions": []}"#).unwrap();
+        let player = Cassette::replay(&path).unwrap();
+        let keys = player.keys(&ApiKeys { openai: Some("sk-real".to_string()), ..ApiKeys::default() });
+        assert_eq!(keys.openai.as_deref(), Some("sk-real"));
+        assert!(keys.anthropic.is_some() && keys.gemini.is_some());
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(Cassette::replay(&path).err().unwrap().contains("not valid"));
+        let _ = std::fs::remove_file(path);
+    }
+}
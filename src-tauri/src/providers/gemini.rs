@@ -0,0 +1,528 @@
+//! Google Gemini `generateContent` / `streamGenerateContent`.
+
+use super::{
+    insert_param, read_sse, send, send_json, Completion, Provider, ProviderError, ProviderRequest,
+    TokenUsage,
+};
+use crate::tools::ToolCall;
+use crate::MessageRole;
+use serde_json::{json, Map, Value};
+use std::time::Duration;
+
+pub const GEMINI_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+
+/// Finish reasons that mean the output was withheld rather than completed
+const BLOCKED_FINISH_REASONS: &[&str] = &[
+    "SAFETY",
+    "RECITATION",
+    "BLOCKLIST",
+    "PROHIBITED_CONTENT",
+    "SPII",
+    "IMAGE_SAFETY",
+];
+
+pub struct GeminiProvider {
+    base_url: String,
+    api_key: String,
+}
+
+impl GeminiProvider {
+    pub fn with_base_url(base_url: &str, api_key: String) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+        }
+    }
+
+    fn body(&self, request: &ProviderRequest) -> Value {
+        let (system, turns) = request.conversation();
+        let contents: Vec<Value> = turns
+            .iter()
+            .map(|turn| {
+                // Gemini calls the assistant side of the conversation "model"
+                let role = if turn.role == MessageRole::Assistant { "model" } else { "user" };
+                json!({ "role": role, "parts": [{ "text": turn.content }] })
+            })
+            .collect();
+        let mut body = json!({ "contents": contents });
+        if let Some(system) = &system {
+            body["systemInstruction"] = json!({ "parts": [{ "text": system }] });
+        }
+        if !request.tools.is_empty() {
+            let declarations: Vec<Value> = request
+                .tools
+                .iter()
+                .map(|tool| {
+                    let mut declaration = json!({ "name": tool.name });
+                    if let Some(description) = &tool.description {
+                        declaration["description"] = json!(description);
+                    }
+                    if !tool.parameters.is_null() {
+                        declaration["parameters"] = convert_schema(&tool.parameters, &tool.parameters, &mut Vec::new());
+                    }
+                    declaration
+                })
+                .collect();
+            body["tools"] = json!([{ "functionDeclarations": declarations }]);
+        }
+
+        let mut generation_config = Map::new();
+        if let Some(params) = &request.parameters {
+            insert_param(&mut generation_config, "temperature", &params.temperature);
+            insert_param(&mut generation_config, "maxOutputTokens", &params.max_tokens);
+            insert_param(&mut generation_config, "topP", &params.top_p);
+            insert_param(&mut generation_config, "frequencyPenalty", &params.frequency_penalty);
+            insert_param(&mut generation_config, "presencePenalty", &params.presence_penalty);
+            insert_param(&mut generation_config, "stopSequences", &params.stop);
+        }
+        if let Some(schema) = &request.response_schema {
+            generation_config.insert("responseMimeType".to_string(), json!("application/json"));
+            generation_config.insert("responseSchema".to_string(), convert_schema(schema, schema, &mut Vec::new()));
+        }
+        if !generation_config.is_empty() {
+            body["generationConfig"] = Value::Object(generation_config);
+        }
+        body
+    }
+
+    fn http(&self, method: &str, timeout: Duration) -> ureq::Request {
+        ureq::post(&format!("{}/models/{}", self.base_url, method))
+            .timeout(timeout)
+            .set("x-goog-api-key", &self.api_key)
+    }
+}
+
+/// Keywords `responseSchema` and function `parameters` accept, an OpenAPI
+/// subset of JSON Schema
+const SCHEMA_KEYWORDS: &[&str] = &[
+    "type", "format", "title", "description", "nullable", "enum", "properties", "required", "items",
+    "minItems", "maxItems", "minimum", "maximum", "minLength", "maxLength", "pattern", "anyOf",
+    "propertyOrdering",
+];
+
+/// Rewrite a JSON Schema into what Gemini accepts: local `$ref`s inlined,
+/// `["string", "null"]` types turned into `nullable`, `const` into a
+/// one-value `enum`, and unsupported keywords (`additionalProperties`,
+/// `$defs`, ...) dropped. A `$ref` met again inside its own expansion
+/// (`expanding`) becomes an unconstrained `{}`, so a recursive schema is
+/// inlined one level deep. Outputs are still validated against the original.
+fn convert_schema(root: &Value, schema: &Value, expanding: &mut Vec<String>) -> Value {
+    let Some(map) = schema.as_object() else {
+        return schema.clone();
+    };
+    if let Some((reference, target)) = map.get("$ref").and_then(Value::as_str).and_then(|reference| {
+        let pointer = reference.strip_prefix('#')?;
+        Some((reference, root.pointer(pointer)?))
+    }) {
+        if expanding.iter().any(|r| r == reference) {
+            return json!({});
+        }
+        expanding.push(reference.to_string());
+        let converted = convert_schema(root, target, expanding);
+        expanding.pop();
+        return converted;
+    }
+
+    let mut converted = Map::new();
+    for (key, value) in map {
+        let value = match key.as_str() {
+            "properties" => Value::Object(
+                value
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .map(|(name, property)| (name.clone(), convert_schema(root, property, expanding)))
+                    .collect(),
+            ),
+            "items" => convert_schema(root, value, expanding),
+            "anyOf" => Value::Array(
+                value.as_array().into_iter().flatten().map(|s| convert_schema(root, s, expanding)).collect(),
+            ),
+            "type" if value.is_array() => {
+                let types: Vec<&Value> = value.as_array().into_iter().flatten().filter(|t| *t != "null").collect();
+                if types.len() < value.as_array().map_or(0, Vec::len) {
+                    converted.insert("nullable".to_string(), json!(true));
+                }
+                types.first().map_or(Value::Null, |t| (*t).clone())
+            }
+            "const" => {
+                converted.insert("enum".to_string(), json!([value]));
+                continue;
+            }
+            key if SCHEMA_KEYWORDS.contains(&key) => value.clone(),
+            _ => continue,
+        };
+        if !value.is_null() {
+            converted.insert(key.clone(), value);
+        }
+    }
+    Value::Object(converted)
+}
+
+/// Turn a rejected prompt or a withheld candidate into a `Blocked` error.
+/// Applies to full responses and to each streamed chunk alike.
+fn check_blocked(data: &Value) -> Result<(), ProviderError> {
+    // The prompt itself was rejected: no candidates at all
+    if let Some(reason) = data["promptFeedback"]["blockReason"].as_str() {
+        return Err(ProviderError::Blocked {
+            reason: reason.to_string(),
+            categories: blocked_categories(&data["promptFeedback"]["safetyRatings"]),
+        });
+    }
+    let candidate = &data["candidates"][0];
+    let finish_reason = candidate["finishReason"].as_str().unwrap_or_default();
+    if BLOCKED_FINISH_REASONS.contains(&finish_reason) {
+        return Err(ProviderError::Blocked {
+            reason: finish_reason.to_string(),
+            categories: blocked_categories(&candidate["safetyRatings"]),
+        });
+    }
+    Ok(())
+}
+
+fn candidate_text(data: &Value) -> String {
+    data["candidates"][0]["content"]["parts"]
+        .as_array()
+        .map(|parts| parts.iter().filter_map(|p| p["text"].as_str()).collect())
+        .unwrap_or_default()
+}
+
+/// `functionCall` parts of the first candidate. Gemini gives calls no id.
+fn candidate_calls(data: &Value) -> Vec<ToolCall> {
+    data["candidates"][0]["content"]["parts"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|part| part.get("functionCall"))
+        .map(|call| ToolCall {
+            id: None,
+            name: call["name"].as_str().unwrap_or_default().to_string(),
+            arguments: call.get("args").cloned().unwrap_or_else(|| json!({})),
+        })
+        .collect()
+}
+
+fn finish_reason(data: &Value) -> Option<String> {
+    data["candidates"][0]["finishReason"].as_str().map(|s| s.to_string())
+}
+
+fn usage_from(data: &Value) -> TokenUsage {
+    let usage = &data["usageMetadata"];
+    let reasoning = usage["thoughtsTokenCount"].as_i64();
+    TokenUsage {
+        input: usage["promptTokenCount"].as_i64(),
+        // Thinking tokens are billed as output but counted separately
+        output: usage["candidatesTokenCount"]
+            .as_i64()
+            .map(|output| output + reasoning.unwrap_or(0)),
+        cached: usage["cachedContentTokenCount"].as_i64(),
+        reasoning,
+        ..Default::default()
+    }
+}
+
+/// Names of the safety categories that were rated as blocking
+fn blocked_categories(ratings: &Value) -> Vec<String> {
+    ratings
+        .as_array()
+        .map(|ratings| {
+            ratings
+                .iter()
+                .filter(|r| r["blocked"] == true)
+                .filter_map(|r| r["category"].as_str().map(|s| s.to_string()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl Provider for GeminiProvider {
+    fn name(&self) -> &'static str {
+        "Gemini"
+    }
+
+    fn complete(&self, request: &ProviderRequest) -> Result<Completion, ProviderError> {
+        let method = format!("{}:generateContent", request.model);
+        let data = send_json(self.http(&method, request.call_timeout()), &self.body(request))?;
+
+        check_blocked(&data)?;
+        if data["candidates"].get(0).is_none() {
+            return Err(ProviderError::InvalidResponse("no candidates returned".to_string()));
+        }
+
+        Ok(Completion {
+            content: candidate_text(&data),
+            usage: usage_from(&data),
+            finish_reason: finish_reason(&data),
+            tool_calls: candidate_calls(&data),
+            ..Default::default()
+        })
+    }
+
+    fn stream(
+        &self,
+        request: &ProviderRequest,
+        on_delta: &mut dyn FnMut(&str),
+    ) -> Result<Completion, ProviderError> {
+        let method = format!("{}:streamGenerateContent?alt=sse", request.model);
+        let response = send(self.http(&method, request.call_timeout()), &self.body(request))?;
+
+        let mut completion = Completion::default();
+        read_sse(response, request.cancel.as_ref(), |_, chunk| {
+            check_blocked(chunk)?;
+            let text = candidate_text(chunk);
+            if !text.is_empty() {
+                completion.content.push_str(&text);
+                on_delta(&text);
+            }
+            // Calls come whole, each in the chunk that makes it
+            completion.tool_calls.extend(candidate_calls(chunk));
+            if let Some(reason) = finish_reason(chunk) {
+                completion.finish_reason = Some(reason);
+            }
+            // Every chunk carries running totals; the last one wins
+            if chunk["usageMetadata"].is_object() {
+                completion.usage = usage_from(chunk);
+            }
+            Ok(())
+        })?;
+        Ok(completion)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parameters::AIParameters;
+    use crate::providers::test_server;
+
+    fn request() -> ProviderRequest {
+        ProviderRequest {
+            model: "gemini-1.5-flash".to_string(),
+            prompt: "Write a haiku".to_string(),
+            system_prompt: Some("You are a poet".to_string()),
+            parameters: Some(AIParameters {
+                temperature: Some(0.9),
+                max_tokens: Some(100),
+                stop: Some(vec!["\n\n".to_string()]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_generate_content_against_local_server() {
+        let server = test_server::respond(
+            200,
+            r#"{"candidates":[{"content":{"parts":[{"text":"Autumn "},{"text":"moon"}]},"finishReason":"STOP"}],
+                "usageMetadata":{"promptTokenCount":7,"candidatesTokenCount":5}}"#,
+        );
+        let provider = GeminiProvider::with_base_url(&server.base_url("/v1beta"), "g-key".to_string());
+
+        let completion = provider.complete(&request()).unwrap();
+        assert_eq!(completion.content, "Autumn moon");
+        assert_eq!(completion.usage, TokenUsage { input: Some(7), output: Some(5), ..Default::default() });
+        assert_eq!(completion.finish_reason.as_deref(), Some("STOP"));
+
+        let captured = server.request();
+        assert_eq!(captured.path, "/v1beta/models/gemini-1.5-flash:generateContent");
+        assert_eq!(captured.header("x-goog-api-key"), Some("g-key"));
+        assert_eq!(captured.body["systemInstruction"]["parts"][0]["text"], "You are a poet");
+        assert_eq!(captured.body["generationConfig"]["maxOutputTokens"], 100);
+        assert_eq!(captured.body["generationConfig"]["stopSequences"][0], "\n\n");
+    }
+
+    #[test]
+    fn test_safety_finish_reason_is_blocked_error() {
+        let server = test_server::respond(
+            200,
+            r#"{"candidates":[{"finishReason":"SAFETY","safetyRatings":[
+                {"category":"HARM_CATEGORY_HARASSMENT","probability":"HIGH","blocked":true},
+                {"category":"HARM_CATEGORY_HATE_SPEECH","probability":"LOW"}]}]}"#,
+        );
+        let provider = GeminiProvider::with_base_url(&server.base_url("/v1beta"), "g-key".to_string());
+
+        match provider.complete(&request()) {
+            Err(ProviderError::Blocked { reason, categories }) => {
+                assert_eq!(reason, "SAFETY");
+                assert_eq!(categories, vec!["HARM_CATEGORY_HARASSMENT".to_string()]);
+            }
+            other => panic!("expected blocked error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_streaming_stops_at_safety_block() {
+        let server = test_server::serve(vec![test_server::CannedResponse {
+            status: 200,
+            headers: vec![("Content-Type".to_string(), "text/event-stream".to_string())],
+            body: [
+                r#"data: {"candidates":[{"content":{"parts":[{"text":"Autumn"}]}}],"usageMetadata":{"promptTokenCount":7,"candidatesTokenCount":1}}"#,
+                r#"data: {"candidates":[{"finishReason":"SAFETY","safetyRatings":[{"category":"HARM_CATEGORY_HARASSMENT","blocked":true}]}]}"#,
+            ]
+            .join("\n\n"),
+        }]);
+        let provider = GeminiProvider::with_base_url(&server.base_url("/v1beta"), "g-key".to_string());
+
+        let mut streamed = String::new();
+        let err = provider
+            .stream(&request(), &mut |text| streamed.push_str(text))
+            .unwrap_err();
+        assert_eq!(streamed, "Autumn");
+        assert_eq!(err.details().kind, "blocked");
+        assert_eq!(
+            server.request().path,
+            "/v1beta/models/gemini-1.5-flash:streamGenerateContent?alt=sse"
+        );
+    }
+
+    #[test]
+    fn test_function_call_parts() {
+        let server = test_server::respond(
+            200,
+            r#"{"candidates":[{"content":{"parts":[{"functionCall":{"name":"get_weather","args":{"city":"Paris"}}}]},"finishReason":"STOP"}]}"#,
+        );
+        let provider = GeminiProvider::with_base_url(&server.base_url("/v1beta"), "g-key".to_string());
+        let request = ProviderRequest {
+            tools: vec![crate::tools::ToolDefinition {
+                name: "get_weather".to_string(),
+                description: Some("Current weather".to_string()),
+                parameters: json!({ "type": "object", "properties": { "city": { "type": "string" } } }),
+            }],
+            ..request()
+        };
+
+        let completion = provider.complete(&request).unwrap();
+        assert_eq!(completion.content, "");
+        assert_eq!(completion.tool_calls[0].name, "get_weather");
+        assert_eq!(completion.tool_calls[0].arguments, json!({ "city": "Paris" }));
+        let declaration = &server.request().body["tools"][0]["functionDeclarations"][0];
+        assert_eq!(declaration["name"], "get_weather");
+        assert_eq!(declaration["parameters"]["properties"]["city"]["type"], "string");
+    }
+
+    #[test]
+    fn test_tool_parameters_rewritten_for_gemini() {
+        let request = ProviderRequest {
+            tools: vec![crate::tools::ToolDefinition {
+                name: "search".to_string(),
+                description: None,
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "query": { "type": "string" },
+                        "filter": { "$ref": "#/$defs/filter" }
+                    },
+                    "additionalProperties": false,
+                    "$defs": {
+                        "filter": {
+                            "type": "object",
+                            "properties": { "any": { "type": "array", "items": { "$ref": "#/$defs/filter" } } },
+                            "additionalProperties": false
+                        }
+                    }
+                }),
+            }],
+            ..request()
+        };
+        let body = GeminiProvider::with_base_url("http://localhost", "g-key".to_string()).body(&request);
+
+        let declaration = &body["tools"][0]["functionDeclarations"][0];
+        assert_eq!(declaration.get("description"), None);
+        let parameters = &declaration["parameters"];
+        assert_eq!(parameters["properties"]["query"], json!({ "type": "string" }));
+        assert_eq!(parameters["properties"]["filter"]["type"], "object");
+        assert!(!parameters.to_string().contains("additionalProperties"));
+        assert!(!parameters.to_string().contains("$ref"));
+    }
+
+    #[test]
+    fn test_recursive_ref_inlined_once() {
+        let schema = json!({
+            "$ref": "#/$defs/node",
+            "$defs": {
+                "node": {
+                    "type": "object",
+                    "properties": {
+                        "value": { "type": "integer" },
+                        "left": { "$ref": "#/$defs/node" },
+                        "right": { "$ref": "#/$defs/node" }
+                    }
+                }
+            }
+        });
+
+        let converted = convert_schema(&schema, &schema, &mut Vec::new());
+        assert_eq!(
+            converted,
+            json!({
+                "type": "object",
+                "properties": { "value": { "type": "integer" }, "left": {}, "right": {} }
+            })
+        );
+    }
+
+    #[test]
+    fn test_response_schema_rewritten_for_gemini() {
+        let schema = json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "type": "object",
+            "properties": {
+                "sentiment": { "const": "positive" },
+                "reason": { "type": ["string", "null"] },
+                "tags": { "type": "array", "items": { "$ref": "#/$defs/tag" } }
+            },
+            "required": ["sentiment"],
+            "additionalProperties": false,
+            "$defs": { "tag": { "type": "string", "maxLength": 20 } }
+        });
+        let request = ProviderRequest { response_schema: Some(schema), ..request() };
+        let body = GeminiProvider::with_base_url("http://localhost", "g-key".to_string()).body(&request);
+
+        let config = &body["generationConfig"];
+        assert_eq!(config["responseMimeType"], "application/json");
+        assert_eq!(
+            config["responseSchema"],
+            json!({
+                "type": "object",
+                "properties": {
+                    "sentiment": { "enum": ["positive"] },
+                    "reason": { "type": "string", "nullable": true },
+                    "tags": { "type": "array", "items": { "type": "string", "maxLength": 20 } }
+                },
+                "required": ["sentiment"]
+            })
+        );
+        assert_eq!(config["temperature"], 0.9);
+    }
+
+    #[test]
+    fn test_blocked_prompt_feedback() {
+        let server = test_server::respond(200, r#"{"promptFeedback":{"blockReason":"PROHIBITED_CONTENT"}}"#);
+        let provider = GeminiProvider::with_base_url(&server.base_url("/v1beta"), "g-key".to_string());
+
+        let err = provider.complete(&request()).unwrap_err();
+        assert_eq!(err.details().kind, "blocked");
+        assert_eq!(err.details().reason.as_deref(), Some("PROHIBITED_CONTENT"));
+    }
+
+    #[test]
+    fn test_conversation_turns_use_gemini_roles() {
+        use crate::PromptMessage;
+        let turn = |role, content: &str| PromptMessage { role, content: content.to_string() };
+        let request = ProviderRequest {
+            messages: vec![
+                turn(MessageRole::System, "Rhyme if you can"),
+                turn(MessageRole::User, "Write a haiku"),
+                turn(MessageRole::Assistant, "Autumn moon"),
+                turn(MessageRole::User, "Another one"),
+            ],
+            ..request()
+        };
+        let body = GeminiProvider::with_base_url("http://localhost", "g-key".to_string()).body(&request);
+        let roles: Vec<&str> = body["contents"].as_array().unwrap().iter().map(|c| c["role"].as_str().unwrap()).collect();
+        assert_eq!(roles, vec!["user", "model", "user"]);
+        assert_eq!(body["contents"][2]["parts"][0]["text"], "Another one");
+        assert_eq!(body["systemInstruction"]["parts"][0]["text"], "You are a poet\n\nRhyme if you can");
+    }
+}
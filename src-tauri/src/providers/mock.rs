@@ -0,0 +1,287 @@
+//! Deterministic stand-in provider for testing datasets, templates and
+//! assertions without calling a real model.
+//!
+//! The model string picks the behaviour:
+//! - `echo` returns the rendered prompt (the last user turn of a conversation)
+//! - `fixture:<path>` answers from a fixture file (see `Fixture`)
+//!
+//! Either can take options after a `?`: `latency=<ms>` delays every call and
+//! `error=<status>` (or `error=transport`) fails it, e.g. `echo?error=429`.
+
+use super::{cancel, Completion, Provider, ProviderError, ProviderRequest, TokenUsage};
+use crate::tools::ToolCall;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Fixture file: the first response whose `match` occurs in the rendered
+/// prompt (the last user turn) answers it; a response without `match` answers anything. A file
+/// that isn't JSON is used as the content of every response. `toolCalls`
+/// lists the calls a response makes, as `{"name", "arguments"}`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Fixture {
+    pub responses: Vec<FixtureResponse>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FixtureResponse {
+    #[serde(rename = "match")]
+    pub pattern: Option<String>,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCall>,
+    pub finish_reason: Option<String>,
+    pub latency_ms: Option<u64>,
+    pub error: Option<FixtureError>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FixtureError {
+    pub status: u16,
+    #[serde(default = "injected_message")]
+    pub message: String,
+}
+
+/// Statuses an injected or fixture error may have
+const ERROR_STATUSES: std::ops::Range<u16> = 400..600;
+
+fn injected_message() -> String {
+    "injected error".to_string()
+}
+
+/// What a mock model string asks for
+#[derive(Debug, Clone, PartialEq)]
+struct MockSpec {
+    fixture: Option<String>,
+    latency: Option<Duration>,
+    error: Option<InjectedError>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum InjectedError {
+    Status(u16),
+    Transport,
+}
+
+fn parse_spec(model: &str) -> Result<MockSpec, ProviderError> {
+    let invalid = |message: String| ProviderError::InvalidParameters(format!("mock model '{}': {}", model, message));
+    let (kind, options) = model.split_once('?').unwrap_or((model, ""));
+    let fixture = match kind {
+        "echo" => None,
+        _ => match kind.strip_prefix("fixture:") {
+            Some(path) if !path.is_empty() => Some(path.to_string()),
+            _ => return Err(invalid("expected 'echo' or 'fixture:<path>'".to_string())),
+        },
+    };
+
+    let mut spec = MockSpec { fixture, latency: None, error: None };
+    for option in options.split('&').filter(|o| !o.is_empty()) {
+        match option.split_once('=') {
+            Some(("latency", ms)) => {
+                let ms = ms.parse::<u64>().map_err(|_| invalid(format!("bad latency '{}'", ms)))?;
+                spec.latency = Some(Duration::from_millis(ms));
+            }
+            Some(("error", "transport")) => spec.error = Some(InjectedError::Transport),
+            Some(("error", status)) => {
+                let status = status
+                    .parse::<u16>()
+                    .ok()
+                    .filter(|s| ERROR_STATUSES.contains(s))
+                    .ok_or_else(|| invalid(format!("bad error status '{}'", status)))?;
+                spec.error = Some(InjectedError::Status(status));
+            }
+            _ => return Err(invalid(format!("unknown option '{}'", option))),
+        }
+    }
+    Ok(spec)
+}
+
+fn load_fixture(path: &str) -> Result<Fixture, ProviderError> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| ProviderError::InvalidParameters(format!("cannot read fixture {}: {}", path, e)))?;
+    match serde_json::from_str::<Fixture>(&content) {
+        Ok(fixture) => {
+            let mut statuses = fixture.responses.iter().filter_map(|r| r.error.as_ref()).map(|e| e.status);
+            if let Some(status) = statuses.find(|s| !ERROR_STATUSES.contains(s)) {
+                return Err(ProviderError::InvalidParameters(format!(
+                    "fixture {}: bad error status {} (expected 400-599)",
+                    path, status
+                )));
+            }
+            Ok(fixture)
+        }
+        Err(_) if serde_json::from_str::<serde_json::Value>(&content).is_ok() => Err(
+            ProviderError::InvalidParameters(format!("fixture {} needs a \"responses\" list", path)),
+        ),
+        Err(_) => Ok(Fixture {
+            responses: vec![FixtureResponse { content, ..Default::default() }],
+        }),
+    }
+}
+
+/// Whitespace-separated words, a stable stand-in for a token count
+fn count_tokens(text: &str) -> i64 {
+    text.split_whitespace().count() as i64
+}
+
+pub struct MockProvider;
+
+impl Provider for MockProvider {
+    fn name(&self) -> &'static str {
+        "Mock"
+    }
+
+    fn complete(&self, request: &ProviderRequest) -> Result<Completion, ProviderError> {
+        let spec = parse_spec(&request.model)?;
+        let prompt = request.last_user_turn();
+        let response = match &spec.fixture {
+            None => FixtureResponse {
+                content: prompt.to_string(),
+                ..Default::default()
+            },
+            Some(path) => load_fixture(path)?
+                .responses
+                .into_iter()
+                .find(|r| r.pattern.as_deref().map_or(true, |p| prompt.contains(p)))
+                .ok_or_else(|| {
+                    ProviderError::InvalidResponse(format!("no response in fixture {} matches the prompt", path))
+                })?,
+        };
+
+        if let Some(latency) = response.latency_ms.map(Duration::from_millis).or(spec.latency) {
+            // Behave like a slow server: give up once the request timeout passes
+            if latency > request.call_timeout() {
+                cancel::pause(request.call_timeout(), request.cancel.as_ref())?;
+                return Err(ProviderError::Timeout("mock latency exceeds the timeout".to_string()));
+            }
+            cancel::pause(latency, request.cancel.as_ref())?;
+        }
+        match (&spec.error, &response.error) {
+            (Some(InjectedError::Transport), _) => {
+                return Err(ProviderError::Transport("injected transport error".to_string()));
+            }
+            (Some(InjectedError::Status(status)), _) => {
+                return Err(ProviderError::Http { status: *status, message: injected_message(), retry_after: None });
+            }
+            (None, Some(error)) => {
+                return Err(ProviderError::Http {
+                    status: error.status,
+                    message: error.message.clone(),
+                    retry_after: None,
+                });
+            }
+            (None, None) => {}
+        }
+
+        let (system, turns) = request.conversation();
+        let input_tokens = system.as_deref().map(count_tokens).unwrap_or(0)
+            + turns.iter().map(|turn| count_tokens(&turn.content)).sum::<i64>();
+        Ok(Completion {
+            usage: TokenUsage {
+                input: Some(input_tokens),
+                output: Some(count_tokens(&response.content)),
+                ..Default::default()
+            },
+            finish_reason: Some(response.finish_reason.unwrap_or_else(|| "stop".to_string())),
+            content: response.content,
+            tool_calls: response.tool_calls,
+            ..Default::default()
+        })
+    }
+
+    /// Deliver the output word by word, so streaming consumers see several chunks
+    fn stream(
+        &self,
+        request: &ProviderRequest,
+        on_delta: &mut dyn FnMut(&str),
+    ) -> Result<Completion, ProviderError> {
+        let completion = self.complete(request)?;
+        for chunk in completion.content.split_inclusive(' ') {
+            on_delta(chunk);
+        }
+        Ok(completion)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(model: &str, prompt: &str) -> ProviderRequest {
+        ProviderRequest {
+            model: model.to_string(),
+            prompt: prompt.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_echo_streams_the_prompt() {
+        let mut chunks = Vec::new();
+        let completion = MockProvider
+            .stream(&request("echo", "Review: great product"), &mut |c| chunks.push(c.to_string()))
+            .unwrap();
+        assert_eq!(completion.content, "Review: great product");
+        assert_eq!(chunks, vec!["Review: ", "great ", "product"]);
+        assert_eq!(completion.usage.input, Some(3));
+        assert_eq!(completion.usage.output, Some(3));
+        assert_eq!(completion.finish_reason.as_deref(), Some("stop"));
+    }
+
+    #[test]
+    fn test_injected_errors() {
+        assert!(matches!(
+            MockProvider.complete(&request("echo?error=503", "Hi")),
+            Err(ProviderError::Http { status: 503, .. })
+        ));
+        assert!(matches!(
+            MockProvider.complete(&request("echo?latency=1&error=transport", "Hi")),
+            Err(ProviderError::Transport(_))
+        ));
+        assert!(matches!(
+            MockProvider.complete(&request("echo?error=200", "Hi")),
+            Err(ProviderError::InvalidParameters(_))
+        ));
+        assert!(matches!(
+            MockProvider.complete(&request("parrot", "Hi")),
+            Err(ProviderError::InvalidParameters(_))
+        ));
+    }
+
+    #[test]
+    fn test_fixture_matches_prompt() {
+        let path = std::env::temp_dir().join(format!("evvl-fixture-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(
+            &path,
+            r#"{"responses": [
+                {"match": "refund", "content": "Contact support", "finishReason": "length"},
+                {"match": "angry", "error": {"status": 429, "message": "Slow down"}},
+                {"content": "Thanks!"}
+            ]}"#,
+        )
+        .unwrap();
+        let model = format!("fixture:{}", path.display());
+
+        let refund = MockProvider.complete(&request(&model, "I want a refund")).unwrap();
+        assert_eq!(refund.content, "Contact support");
+        assert_eq!(refund.finish_reason.as_deref(), Some("length"));
+        match MockProvider.complete(&request(&model, "I am angry")) {
+            Err(ProviderError::Http { status, message, .. }) => assert_eq!((status, message.as_str()), (429, "Slow down")),
+            other => panic!("expected the fixture error, got {:?}", other),
+        }
+        assert_eq!(MockProvider.complete(&request(&model, "Hello")).unwrap().content, "Thanks!");
+
+        // Plain text fixtures answer every prompt
+        std::fs::write(&path, "Always this").unwrap();
+        assert_eq!(MockProvider.complete(&request(&model, "Hello")).unwrap().content, "Always this");
+
+        // A fixture error must be a failure status, like `?error=`
+        std::fs::write(&path, r#"{"responses": [{"error": {"status": 200}}]}"#).unwrap();
+        assert!(matches!(
+            MockProvider.complete(&request(&model, "Hello")),
+            Err(ProviderError::InvalidParameters(_))
+        ));
+        let _ = std::fs::remove_file(path);
+    }
+}
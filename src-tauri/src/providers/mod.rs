@@ -0,0 +1,860 @@
+//! Model providers for the Rust backend.
+//!
+//! Every `provider` string a `ProjectModelConfig` can carry maps to a
+//! `Provider` implementation here, so the CLI runner and the Tauri commands
+//! share one execution path.
+
+use crate::parameters::AIParameters;
+use crate::tools::{ToolCall, ToolDefinition};
+use crate::{ApiKeys, MessageRole, PromptMessage};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::io::{BufRead, BufReader};
+use std::time::{Duration, Instant};
+
+mod anthropic;
+mod cancel;
+pub mod cassette;
+mod gemini;
+mod mock;
+mod ollama;
+mod openai;
+mod openrouter;
+mod retry;
+
+pub use anthropic::{AnthropicProvider, DEFAULT_MAX_TOKENS as ANTHROPIC_DEFAULT_MAX_TOKENS};
+pub use cancel::{generate_cancellable, CancelToken};
+pub use gemini::GeminiProvider;
+pub use mock::MockProvider;
+pub use ollama::{OllamaModel, OllamaProvider};
+pub use openai::OpenAiProvider;
+pub use openrouter::OpenRouterProvider;
+pub use retry::RetryPolicy;
+
+/// Default timeout for a single provider call
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// A single generation request, already rendered
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProviderRequest {
+    pub model: String,
+    pub prompt: String,
+    #[serde(rename = "systemPrompt")]
+    pub system_prompt: Option<String>,
+    pub parameters: Option<AIParameters>,
+    /// Turns of a conversation prompt, sent instead of `prompt`. Left out
+    /// of the serialized request when empty, so single prompts keep their
+    /// cache keys.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub messages: Vec<PromptMessage>,
+    /// Functions the model may call, likewise left out when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<ToolDefinition>,
+    /// JSON Schema the output must follow, for providers with a native
+    /// structured output mode
+    #[serde(rename = "responseSchema", default, skip_serializing_if = "Option::is_none")]
+    pub response_schema: Option<Value>,
+    /// Limit for a single attempt, `REQUEST_TIMEOUT` when not set. Not part
+    /// of what the provider sees, so not serialized.
+    #[serde(skip)]
+    pub timeout: Option<Duration>,
+    /// Stops the call early once cancelled, likewise not serialized
+    #[serde(skip)]
+    pub cancel: Option<CancelToken>,
+}
+
+impl ProviderRequest {
+    /// How long one attempt may take
+    pub fn call_timeout(&self) -> Duration {
+        self.timeout.unwrap_or(REQUEST_TIMEOUT)
+    }
+
+    /// The request as a conversation: the system prompt followed by any
+    /// system turns, joined, and the user/assistant turns in order. A single
+    /// prompt is one user turn.
+    pub fn conversation(&self) -> (Option<String>, Vec<PromptMessage>) {
+        if self.messages.is_empty() {
+            let turn = PromptMessage { role: MessageRole::User, content: self.prompt.clone() };
+            return (self.system_prompt.clone(), vec![turn]);
+        }
+        let (system_turns, turns): (Vec<&PromptMessage>, Vec<&PromptMessage>) =
+            self.messages.iter().partition(|m| m.role == MessageRole::System);
+        let system: Vec<&str> = self
+            .system_prompt
+            .iter()
+            .map(String::as_str)
+            .chain(system_turns.iter().map(|m| m.content.as_str()))
+            .collect();
+        let system = (!system.is_empty()).then(|| system.join("\n\n"));
+        (system, turns.into_iter().cloned().collect())
+    }
+
+    /// Text of the last user turn, what a single prompt would have been
+    pub fn last_user_turn(&self) -> &str {
+        self.messages
+            .iter()
+            .rev()
+            .find(|m| m.role == MessageRole::User)
+            .map_or(self.prompt.as_str(), |m| m.content.as_str())
+    }
+}
+
+/// Outcome of a request as recorded on a result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderResponse {
+    pub content: String,
+    /// Total tokens (input + output), as shown by the GUI
+    pub tokens: Option<i64>,
+    #[serde(rename = "inputTokens")]
+    pub input_tokens: Option<i64>,
+    #[serde(rename = "outputTokens")]
+    pub output_tokens: Option<i64>,
+    #[serde(rename = "cachedTokens")]
+    pub cached_tokens: Option<i64>,
+    #[serde(rename = "cacheWriteTokens", default, skip_serializing_if = "Option::is_none")]
+    pub cache_write_tokens: Option<i64>,
+    #[serde(rename = "reasoningTokens")]
+    pub reasoning_tokens: Option<i64>,
+    #[serde(rename = "finishReason")]
+    pub finish_reason: Option<String>,
+    /// Cost in USD as billed by the provider, when it reports one
+    pub cost: Option<f64>,
+    /// Provider that actually served a routed request (OpenRouter)
+    #[serde(rename = "upstreamProvider")]
+    pub upstream_provider: Option<String>,
+    /// Tool calls the model made, in order
+    #[serde(rename = "toolCalls", default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    pub latency: i64,
+    /// Calls made, including retries
+    #[serde(default = "one")]
+    pub attempts: u32,
+    pub error: Option<String>,
+    #[serde(rename = "errorDetails")]
+    pub error_details: Option<ErrorDetails>,
+}
+
+fn one() -> u32 {
+    1
+}
+
+/// Machine-readable side of a failed request, stored next to the message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorDetails {
+    pub kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub categories: Vec<String>,
+}
+
+/// Token counts reported by the provider
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TokenUsage {
+    /// Prompt tokens, including any served from cache
+    pub input: Option<i64>,
+    /// Completion tokens, including any spent on reasoning
+    pub output: Option<i64>,
+    /// Part of `input` read from the provider's prompt cache
+    pub cached: Option<i64>,
+    /// Part of `input` written to the prompt cache (Anthropic), billed
+    /// above the input price
+    pub cache_writes: Option<i64>,
+    /// Part of `output` spent on hidden reasoning
+    pub reasoning: Option<i64>,
+}
+
+impl TokenUsage {
+    pub fn total(&self) -> Option<i64> {
+        match (self.input, self.output) {
+            (None, None) => None,
+            (i, o) => Some(i.unwrap_or(0) + o.unwrap_or(0)),
+        }
+    }
+}
+
+/// What a provider returns on success
+#[derive(Debug, Clone, Default)]
+pub struct Completion {
+    pub content: String,
+    pub usage: TokenUsage,
+    /// Why generation stopped, in the provider's own words
+    /// (`stop`, `length`, `max_tokens`, `end_turn`, `MAX_TOKENS`, ...)
+    pub finish_reason: Option<String>,
+    pub cost: Option<f64>,
+    pub upstream_provider: Option<String>,
+    pub tool_calls: Vec<ToolCall>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ProviderError {
+    /// No API key configured for the provider
+    MissingApiKey(&'static str),
+    /// The provider string has no Rust implementation
+    Unsupported(String),
+    /// The provider answered with an error status
+    Http {
+        status: u16,
+        message: String,
+        /// Delay requested by a `Retry-After` header
+        retry_after: Option<Duration>,
+    },
+    /// The request never got a response (DNS, TLS, connection reset, ...)
+    Transport(String),
+    /// No complete response within the request timeout or the run deadline
+    Timeout(String),
+    /// The response could not be understood
+    InvalidResponse(String),
+    /// The provider withheld the output (safety filters, recitation, ...)
+    Blocked { reason: String, categories: Vec<String> },
+    /// A parameter is outside the range the providers accept
+    InvalidParameters(String),
+    /// Replaying a cassette that has no (unused) exchange for the request
+    NotRecorded(String),
+    /// The run was cancelled while the call was in flight
+    Cancelled,
+}
+
+impl ProviderError {
+    pub fn details(&self) -> ErrorDetails {
+        let (kind, status, reason, categories) = match self {
+            ProviderError::MissingApiKey(_) => ("missing_api_key", None, None, vec![]),
+            ProviderError::Unsupported(_) => ("unsupported", None, None, vec![]),
+            ProviderError::Http { status, .. } => ("http", Some(*status), None, vec![]),
+            ProviderError::Transport(_) => ("transport", None, None, vec![]),
+            ProviderError::Timeout(_) => ("timeout", None, None, vec![]),
+            ProviderError::InvalidResponse(_) => ("invalid_response", None, None, vec![]),
+            ProviderError::Blocked { reason, categories } => {
+                ("blocked", None, Some(reason.clone()), categories.clone())
+            }
+            ProviderError::InvalidParameters(_) => ("invalid_parameters", None, None, vec![]),
+            ProviderError::NotRecorded(_) => ("not_recorded", None, None, vec![]),
+            ProviderError::Cancelled => ("cancelled", None, None, vec![]),
+        };
+        ErrorDetails {
+            kind: kind.to_string(),
+            status,
+            reason,
+            categories,
+        }
+    }
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProviderError::MissingApiKey(env_var) => write!(f, "API key not set ({})", env_var),
+            ProviderError::Unsupported(provider) => {
+                write!(f, "Provider '{}' is not supported by the CLI yet", provider)
+            }
+            ProviderError::Http { status, message, .. } => write!(f, "API error ({}): {}", status, message),
+            ProviderError::Transport(message) => write!(f, "request failed: {}", message),
+            ProviderError::Timeout(message) => write!(f, "timed out ({})", message),
+            ProviderError::InvalidResponse(message) => write!(f, "returned an invalid response: {}", message),
+            ProviderError::Blocked { reason, categories } if categories.is_empty() => {
+                write!(f, "blocked the response ({})", reason)
+            }
+            ProviderError::Blocked { reason, categories } => {
+                write!(f, "blocked the response ({}: {})", reason, categories.join(", "))
+            }
+            ProviderError::InvalidParameters(message) => write!(f, "Invalid parameters: {}", message),
+            ProviderError::NotRecorded(request) => {
+                write!(f, "has no recorded response for {} in the cassette", request)
+            }
+            ProviderError::Cancelled => write!(f, "Request cancelled"),
+        }
+    }
+}
+
+pub trait Provider: Send + Sync {
+    /// Display name used to prefix error messages
+    fn name(&self) -> &'static str;
+
+    fn complete(&self, request: &ProviderRequest) -> Result<Completion, ProviderError>;
+
+    /// Like `complete`, but hands each text chunk to `on_delta` as it
+    /// arrives. Providers without streaming deliver the whole output as a
+    /// single chunk.
+    fn stream(
+        &self,
+        request: &ProviderRequest,
+        on_delta: &mut dyn FnMut(&str),
+    ) -> Result<Completion, ProviderError> {
+        let completion = self.complete(request)?;
+        on_delta(&completion.content);
+        Ok(completion)
+    }
+}
+
+/// Look up the implementation for a `ProjectModelConfig.provider` string
+pub fn provider_for(provider: &str, keys: &ApiKeys) -> Result<Box<dyn Provider>, ProviderError> {
+    match provider {
+        "openai" => match std::env::var("OPENAI_BASE_URL") {
+            // A custom endpoint (vLLM, a proxy, a test stand-in) may not need a key
+            Ok(base_url) => Ok(Box::new(OpenAiProvider::with_base_url(
+                "OpenAI",
+                &base_url,
+                keys.openai.clone(),
+            ))),
+            Err(_) => {
+                let key = keys.openai.clone().ok_or(ProviderError::MissingApiKey("OPENAI_API_KEY"))?;
+                Ok(Box::new(OpenAiProvider::new(key)))
+            }
+        },
+        "lmstudio" => {
+            let base_url = std::env::var("LMSTUDIO_BASE_URL")
+                .unwrap_or_else(|_| openai::LMSTUDIO_BASE_URL.to_string());
+            Ok(Box::new(OpenAiProvider::with_base_url("LM Studio", &base_url, None)))
+        }
+        "anthropic" => {
+            let key = keys
+                .anthropic
+                .clone()
+                .ok_or(ProviderError::MissingApiKey("ANTHROPIC_API_KEY"))?;
+            let base_url = std::env::var("ANTHROPIC_BASE_URL")
+                .unwrap_or_else(|_| anthropic::ANTHROPIC_BASE_URL.to_string());
+            Ok(Box::new(AnthropicProvider::with_base_url(&base_url, key)))
+        }
+        "gemini" => {
+            let key = keys
+                .gemini
+                .clone()
+                .ok_or(ProviderError::MissingApiKey("GOOGLE_API_KEY or GEMINI_API_KEY"))?;
+            let base_url = std::env::var("GEMINI_BASE_URL")
+                .unwrap_or_else(|_| gemini::GEMINI_BASE_URL.to_string());
+            Ok(Box::new(GeminiProvider::with_base_url(&base_url, key)))
+        }
+        "openrouter" => {
+            let key = keys
+                .openrouter
+                .clone()
+                .ok_or(ProviderError::MissingApiKey("OPENROUTER_API_KEY"))?;
+            let base_url = std::env::var("OPENROUTER_BASE_URL")
+                .unwrap_or_else(|_| openrouter::OPENROUTER_BASE_URL.to_string());
+            Ok(Box::new(OpenRouterProvider::with_base_url(&base_url, key)))
+        }
+        "ollama" => Ok(Box::new(OllamaProvider::from_env())),
+        "mock" => Ok(Box::new(MockProvider)),
+        other => Err(ProviderError::Unsupported(other.to_string())),
+    }
+}
+
+/// Run a request against a provider, retrying transient failures per
+/// `retry`, timing it and folding any failure into the response's `error`
+/// field. With `on_delta` the output is streamed; a call that already
+/// streamed output is not retried, so deltas are never repeated.
+pub fn execute(
+    provider: &dyn Provider,
+    request: &ProviderRequest,
+    retry: &RetryPolicy,
+    mut on_delta: Option<&mut dyn FnMut(&str)>,
+) -> ProviderResponse {
+    // A replayed call streams or not as it was recorded, or its body won't parse
+    let replay_streaming = cassette::active()
+        .filter(|_| cassette::replaying())
+        .map(|cassette| cassette.recorded_streaming());
+    let mut attempts = 0;
+    let (outcome, latency) = loop {
+        // Each attempt gets the request timeout, cut short by the run deadline
+        if let Err(e) = cancel::check(request.cancel.as_ref()) {
+            break (Err(e), 0);
+        }
+        let left = retry.deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        if left == Some(Duration::ZERO) {
+            break (Err(ProviderError::Timeout("run deadline reached".to_string())), 0);
+        }
+        let timeout = request.call_timeout().min(left.unwrap_or(Duration::MAX));
+        let mut attempt = request.clone();
+        attempt.timeout = Some(timeout);
+
+        attempts += 1;
+        let started = Instant::now();
+        let mut streamed = false;
+        let outcome = match (on_delta.as_mut(), replay_streaming) {
+            (None, Some(true)) => provider.stream(&attempt, &mut |_| {}),
+            (Some(on_delta), Some(false)) => provider.complete(&attempt).inspect(|completion| {
+                streamed = true;
+                on_delta(&completion.content)
+            }),
+            (Some(on_delta), _) => provider.stream(&attempt, &mut |text| {
+                streamed = true;
+                on_delta(text)
+            }),
+            (None, _) => provider.complete(&attempt),
+        };
+        let latency = started.elapsed().as_millis() as i64;
+        let outcome = outcome.map_err(|e| match e {
+            ProviderError::Timeout(_) if left.is_some_and(|left| left <= request.call_timeout()) => {
+                ProviderError::Timeout("run deadline reached".to_string())
+            }
+            ProviderError::Timeout(_) => ProviderError::Timeout(format!("no response within {:?}", timeout)),
+            e => e,
+        });
+
+        match outcome {
+            Err(e) if attempts < retry.max_attempts && !streamed && retry::is_retryable(&e) => {
+                let retry_after = match &e {
+                    ProviderError::Http { retry_after, .. } => *retry_after,
+                    _ => None,
+                };
+                let Some(delay) = retry.delay(attempts, retry_after) else {
+                    break (Err(retry::retry_after_too_long(e)), latency);
+                };
+                if retry.deadline.is_some_and(|deadline| Instant::now() + delay >= deadline) {
+                    break (Err(e), latency);
+                }
+                // A replayed failure is retried at once; there is no rate limit to wait out
+                if !cassette::replaying() {
+                    if let Err(e) = cancel::pause(delay, request.cancel.as_ref()) {
+                        break (Err(e), latency);
+                    }
+                }
+            }
+            outcome => break (outcome, latency),
+        }
+    };
+
+    match outcome {
+        Ok(completion) => ProviderResponse {
+            content: completion.content,
+            tokens: completion.usage.total(),
+            input_tokens: completion.usage.input,
+            output_tokens: completion.usage.output,
+            cached_tokens: completion.usage.cached,
+            cache_write_tokens: completion.usage.cache_writes,
+            reasoning_tokens: completion.usage.reasoning,
+            finish_reason: completion.finish_reason,
+            cost: completion.cost,
+            upstream_provider: completion.upstream_provider,
+            tool_calls: completion.tool_calls,
+            latency,
+            attempts,
+            error: None,
+            error_details: None,
+        },
+        Err(e) => {
+            let mut error = format!("{} {}", provider.name(), e);
+            if attempts > 1 {
+                error.push_str(&format!(" (after {} attempts)", attempts));
+            }
+            error_response(error, e.details(), latency, attempts)
+        }
+    }
+}
+
+/// Validate the parameters, resolve the provider and execute in one step;
+/// these early errors (bad parameters, unknown provider, missing key) are
+/// reported the same way as call errors
+pub fn generate(
+    provider: &str,
+    keys: &ApiKeys,
+    request: &ProviderRequest,
+    retry: &RetryPolicy,
+    on_delta: Option<&mut dyn FnMut(&str)>,
+) -> ProviderResponse {
+    if let Some(Err(message)) = request.parameters.as_ref().map(AIParameters::validate) {
+        let e = ProviderError::InvalidParameters(message);
+        return error_response(e.to_string(), e.details(), 0, 0);
+    }
+    let keys = match cassette::active() {
+        Some(cassette) => cassette.keys(keys),
+        None => keys.clone(),
+    };
+    match provider_for(provider, &keys) {
+        Ok(p) => execute(p.as_ref(), request, retry, on_delta),
+        Err(e) => error_response(e.to_string(), e.details(), 0, 0),
+    }
+}
+
+fn error_response(error: String, details: ErrorDetails, latency: i64, attempts: u32) -> ProviderResponse {
+    ProviderResponse {
+        content: String::new(),
+        tokens: None,
+        input_tokens: None,
+        output_tokens: None,
+        cached_tokens: None,
+        cache_write_tokens: None,
+        reasoning_tokens: None,
+        finish_reason: None,
+        cost: None,
+        upstream_provider: None,
+        tool_calls: Vec::new(),
+        latency,
+        attempts,
+        error: Some(error),
+        error_details: Some(details),
+    }
+}
+
+/// Whether a finish reason means the output was cut off by the token limit
+pub fn is_truncated(finish_reason: &str) -> bool {
+    matches!(finish_reason, "length" | "max_tokens" | "MAX_TOKENS")
+}
+
+/// Copy a parameter into a request body under the provider's name for it
+pub(crate) fn insert_param<T: Serialize>(target: &mut Map<String, Value>, key: &str, value: &Option<T>) {
+    if let Some(value) = value {
+        target.insert(key.to_string(), serde_json::json!(value));
+    }
+}
+
+/// POST a JSON body, turning HTTP error statuses into the provider's error
+/// message where one is available. Goes through the cassette when one is
+/// installed.
+pub(crate) fn send(request: ureq::Request, body: &Value) -> Result<ureq::Response, ProviderError> {
+    if let Some(cassette) = cassette::active() {
+        return cassette.exchange(request, body);
+    }
+    match request.send_json(body.clone()) {
+        Ok(response) => Ok(response),
+        Err(ureq::Error::Status(status, response)) => Err(status_error(status, response)),
+        Err(e) => Err(transport_error(&e)),
+    }
+}
+
+/// Classify a failure to get or read a response. ureq reports an expired
+/// timeout as an I/O error of kind `TimedOut` (`WouldBlock` on some
+/// platforms), possibly wrapped.
+pub(crate) fn transport_error(error: &(dyn std::error::Error + 'static)) -> ProviderError {
+    let mut source = Some(error);
+    while let Some(e) = source {
+        let timed_out = e
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|io| matches!(io.kind(), std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock));
+        if timed_out {
+            return ProviderError::Timeout(error.to_string());
+        }
+        source = e.source();
+    }
+    ProviderError::Transport(error.to_string())
+}
+
+/// Build the error for a response with an error status
+fn status_error(status: u16, response: ureq::Response) -> ProviderError {
+    let retry_after = response.header("retry-after").and_then(retry::parse_retry_after);
+    let text = response.into_string().unwrap_or_default();
+    // Most APIs nest the message under `error.message`; some
+    // compatible servers send a bare `error` string instead
+    let message = serde_json::from_str::<Value>(&text)
+        .ok()
+        .and_then(|v| {
+            v["error"]["message"]
+                .as_str()
+                .or_else(|| v["error"].as_str())
+                .map(|s| s.to_string())
+        })
+        .unwrap_or(text);
+    ProviderError::Http { status, message, retry_after }
+}
+
+/// POST a JSON body and return the parsed JSON response
+pub(crate) fn send_json(request: ureq::Request, body: &Value) -> Result<Value, ProviderError> {
+    send(request, body)?
+        .into_json::<Value>()
+        .map_err(|e| match transport_error(&e) {
+            ProviderError::Timeout(message) => ProviderError::Timeout(message),
+            _ => ProviderError::InvalidResponse(e.to_string()),
+        })
+}
+
+/// Read a server-sent event stream, calling `on_event` with the event name
+/// (if any) and the JSON payload of each `data:` block. Stops at `[DONE]`,
+/// or with `Cancelled` at the first line after `cancel` is cancelled, which
+/// drops the response and closes the connection.
+pub(crate) fn read_sse(
+    response: ureq::Response,
+    cancel: Option<&CancelToken>,
+    mut on_event: impl FnMut(Option<&str>, &Value) -> Result<(), ProviderError>,
+) -> Result<(), ProviderError> {
+    let reader = BufReader::new(response.into_reader());
+    let mut event: Option<String> = None;
+    let mut data = String::new();
+
+    let mut dispatch = |event: &Option<String>, data: &mut String| -> Result<bool, ProviderError> {
+        if data.is_empty() {
+            return Ok(true);
+        }
+        if data.trim() == "[DONE]" {
+            return Ok(false);
+        }
+        let payload: Value = serde_json::from_str(data)
+            .map_err(|e| ProviderError::InvalidResponse(format!("bad stream chunk: {}", e)))?;
+        data.clear();
+        on_event(event.as_deref(), &payload)?;
+        Ok(true)
+    };
+
+    for line in reader.lines() {
+        let line = line.map_err(|e| transport_error(&e))?;
+        cancel::check(cancel)?;
+        if line.is_empty() {
+            if !dispatch(&event, &mut data)? {
+                return Ok(());
+            }
+            event = None;
+        } else if let Some(name) = line.strip_prefix("event:") {
+            event = Some(name.trim().to_string());
+        } else if let Some(chunk) = line.strip_prefix("data:") {
+            if !data.is_empty() {
+                data.push('\n');
+            }
+            data.push_str(chunk.trim_start());
+        }
+        // Comment lines (": keep-alive") and unknown fields are ignored
+    }
+    dispatch(&event, &mut data)?;
+    Ok(())
+}
+
+/// Minimal HTTP server standing in for a provider API in tests
+#[cfg(test)]
+pub(crate) mod test_server {
+    use serde_json::Value;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{SocketAddr, TcpListener};
+    use std::sync::mpsc::{self, Receiver};
+    use std::thread;
+
+    pub struct CannedResponse {
+        pub status: u16,
+        pub headers: Vec<(String, String)>,
+        pub body: String,
+    }
+
+    #[derive(Debug)]
+    pub struct CapturedRequest {
+        pub method: String,
+        pub path: String,
+        pub headers: Vec<(String, String)>,
+        pub body: Value,
+    }
+
+    impl CapturedRequest {
+        pub fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.as_str())
+        }
+    }
+
+    pub struct TestServer {
+        addr: SocketAddr,
+        requests: Receiver<CapturedRequest>,
+    }
+
+    impl TestServer {
+        pub fn base_url(&self, prefix: &str) -> String {
+            format!("http://{}{}", self.addr, prefix)
+        }
+
+        /// Next request the server received, in arrival order
+        pub fn request(&self) -> CapturedRequest {
+            self.requests.recv().expect("server received no request")
+        }
+    }
+
+    /// Serve a single JSON response
+    pub fn respond(status: u16, body: &str) -> TestServer {
+        serve(vec![CannedResponse {
+            status,
+            headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+            body: body.to_string(),
+        }])
+    }
+
+    /// Serve the given responses to consecutive connections
+    pub fn serve(responses: Vec<CannedResponse>) -> TestServer {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            for response in responses {
+                let Ok((stream, _)) = listener.accept() else { return };
+                let mut reader = BufReader::new(stream);
+
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut parts = request_line.split_whitespace();
+                let method = parts.next().unwrap_or_default().to_string();
+                let path = parts.next().unwrap_or_default().to_string();
+
+                let mut headers = Vec::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some((k, v)) = line.split_once(':') {
+                        headers.push((k.trim().to_string(), v.trim().to_string()));
+                    }
+                }
+
+                let length = headers
+                    .iter()
+                    .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+                    .and_then(|(_, v)| v.parse::<usize>().ok())
+                    .unwrap_or(0);
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+
+                let _ = tx.send(CapturedRequest {
+                    method,
+                    path,
+                    headers,
+                    body: serde_json::from_slice(&body).unwrap_or(Value::Null),
+                });
+
+                let mut stream = reader.into_inner();
+                let mut head = format!(
+                    "HTTP/1.1 {} Canned\r\nContent-Length: {}\r\nConnection: close\r\n",
+                    response.status,
+                    response.body.len()
+                );
+                for (k, v) in &response.headers {
+                    head.push_str(&format!("{}: {}\r\n", k, v));
+                }
+                head.push_str("\r\n");
+                let _ = stream.write_all(head.as_bytes());
+                let _ = stream.write_all(response.body.as_bytes());
+            }
+        });
+
+        TestServer { addr, requests: rx }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_for_requires_key() {
+        assert!(matches!(
+            provider_for("openai", &ApiKeys::default()),
+            Err(ProviderError::MissingApiKey("OPENAI_API_KEY"))
+        ));
+
+        let keys = ApiKeys { anthropic: Some("sk-ant".to_string()), ..ApiKeys::default() };
+        assert_eq!(provider_for("anthropic", &keys).unwrap().name(), "Anthropic");
+    }
+
+    #[test]
+    fn test_generate_reports_unsupported_provider() {
+        let request = ProviderRequest {
+            model: "x".to_string(),
+            prompt: "Hello".to_string(),
+            ..Default::default()
+        };
+
+        let response = generate("nope", &ApiKeys::default(), &request, &RetryPolicy::default(), None);
+        assert_eq!(response.content, "");
+        assert!(response.error.unwrap().contains("'nope' is not supported"));
+    }
+
+    #[test]
+    fn test_generate_rejects_out_of_range_parameters() {
+        let request = ProviderRequest {
+            parameters: Some(AIParameters { temperature: Some(3.0), ..Default::default() }),
+            ..request()
+        };
+
+        let response = generate("openai", &ApiKeys::default(), &request, &RetryPolicy::default(), None);
+        assert_eq!(response.error_details.unwrap().kind, "invalid_parameters");
+        assert!(response.error.unwrap().contains("temperature must be between 0 and 2"));
+    }
+
+    fn fast_retries(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            deadline: None,
+        }
+    }
+
+    fn request() -> ProviderRequest {
+        ProviderRequest {
+            model: "gpt-4o".to_string(),
+            prompt: "Hello".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_execute_retries_rate_limit_with_retry_after() {
+        let server = test_server::serve(vec![
+            test_server::CannedResponse {
+                status: 429,
+                headers: vec![("Retry-After".to_string(), "0".to_string())],
+                body: r#"{"error":{"message":"Rate limit reached"}}"#.to_string(),
+            },
+            test_server::CannedResponse {
+                status: 200,
+                headers: vec![],
+                body: r#"{"choices":[{"message":{"content":"Hi"}}]}"#.to_string(),
+            },
+        ]);
+        let provider = OpenAiProvider::with_base_url("OpenAI", &server.base_url("/v1"), None);
+
+        let response = execute(&provider, &request(), &fast_retries(3), None);
+        assert_eq!(response.error, None);
+        assert_eq!(response.content, "Hi");
+        assert_eq!(response.attempts, 2);
+    }
+
+    #[test]
+    fn test_execute_gives_up_after_max_attempts() {
+        let server = test_server::serve(vec![
+            test_server::CannedResponse { status: 503, headers: vec![], body: "unavailable".to_string() },
+            test_server::CannedResponse { status: 503, headers: vec![], body: "unavailable".to_string() },
+        ]);
+        let provider = OpenAiProvider::with_base_url("OpenAI", &server.base_url("/v1"), None);
+
+        let response = execute(&provider, &request(), &fast_retries(2), None);
+        assert_eq!(response.attempts, 2);
+        assert!(response.error.unwrap().ends_with("(after 2 attempts)"));
+        assert_eq!(response.error_details.unwrap().status, Some(503));
+    }
+
+    #[test]
+    fn test_execute_does_not_retry_client_errors() {
+        let server = test_server::respond(400, r#"{"error":{"message":"bad request"}}"#);
+        let provider = OpenAiProvider::with_base_url("OpenAI", &server.base_url("/v1"), None);
+
+        let response = execute(&provider, &request(), &fast_retries(3), None);
+        assert_eq!(response.attempts, 1);
+        assert!(response.error.is_some());
+    }
+
+    #[test]
+    fn test_execute_times_out_hung_server() {
+        // Accepts connections but never answers
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}/v1", listener.local_addr().unwrap());
+        let provider = OpenAiProvider::with_base_url("OpenAI", &base_url, None);
+        let request = ProviderRequest { timeout: Some(Duration::from_millis(100)), ..request() };
+
+        let response = execute(&provider, &request, &fast_retries(1), None);
+        assert_eq!(response.error_details.unwrap().kind, "timeout");
+        assert_eq!(response.error.unwrap(), "OpenAI timed out (no response within 100ms)");
+        drop(listener);
+    }
+
+    #[test]
+    fn test_execute_stops_at_run_deadline() {
+        let provider = OpenAiProvider::with_base_url("OpenAI", "http://127.0.0.1:9/v1", None);
+        let retry = RetryPolicy { deadline: Some(Instant::now()), ..fast_retries(3) };
+
+        let response = execute(&provider, &request(), &retry, None);
+        assert_eq!(response.attempts, 0);
+        assert_eq!(response.error_details.unwrap().kind, "timeout");
+        assert!(response.error.unwrap().contains("run deadline reached"));
+    }
+}
@@ -0,0 +1,332 @@
+//! Ollama native API (`/api/chat`, `/api/tags`) for fully offline runs
+//! against a local daemon.
+
+use super::{
+    cancel, insert_param, send, send_json, transport_error, Completion, Provider, ProviderError, ProviderRequest,
+    TokenUsage,
+};
+use crate::tools::ToolCall;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::io::{BufRead, BufReader};
+use std::time::Duration;
+
+const DEFAULT_PORT: u16 = 11434;
+const TAGS_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// An installed model as reported by `/api/tags`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OllamaModel {
+    pub name: String,
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub modified_at: Option<String>,
+    #[serde(default)]
+    pub details: Option<OllamaModelDetails>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OllamaModelDetails {
+    pub family: Option<String>,
+    pub parameter_size: Option<String>,
+    pub quantization_level: Option<String>,
+}
+
+/// Turn an `OLLAMA_HOST` value into a base URL. A bare host (`0.0.0.0`,
+/// `gpu-box:8080`, `[::1]`) gets `http://` and, without a port, Ollama's
+/// 11434. A URL with a scheme keeps its own port: 80 for http, 443 for https.
+pub fn base_url_from_host(host: Option<&str>) -> String {
+    let host = host.map(str::trim).filter(|h| !h.is_empty()).unwrap_or("localhost");
+    let host = host.trim_end_matches('/');
+    if host.contains("://") {
+        return host.to_string();
+    }
+    let (authority, path) = match host.find('/') {
+        Some(index) => host.split_at(index),
+        None => (host, ""),
+    };
+    let authority = if authority.starts_with('[') {
+        if authority.contains("]:") {
+            authority.to_string()
+        } else {
+            format!("{}:{}", authority, DEFAULT_PORT)
+        }
+    } else {
+        match authority.matches(':').count() {
+            0 => format!("{}:{}", authority, DEFAULT_PORT),
+            1 => authority.to_string(),
+            // A bare IPv6 address
+            _ => format!("[{}]:{}", authority, DEFAULT_PORT),
+        }
+    };
+    format!("http://{}{}", authority, path)
+}
+
+pub struct OllamaProvider {
+    base_url: String,
+}
+
+impl OllamaProvider {
+    pub fn new(base_url: String) -> Self {
+        Self { base_url }
+    }
+
+    /// Provider pointed at `OLLAMA_HOST` (or the default local daemon)
+    pub fn from_env() -> Self {
+        Self::new(base_url_from_host(std::env::var("OLLAMA_HOST").ok().as_deref()))
+    }
+
+    /// Models installed in the daemon
+    pub fn list_models(&self) -> Result<Vec<OllamaModel>, ProviderError> {
+        let response = ureq::get(&format!("{}/api/tags", self.base_url))
+            .timeout(TAGS_TIMEOUT)
+            .call()
+            .map_err(|e| self.connection_error(e))?;
+        let data: Value = response
+            .into_json()
+            .map_err(|e| ProviderError::InvalidResponse(e.to_string()))?;
+        serde_json::from_value(data["models"].clone())
+            .map_err(|e| ProviderError::InvalidResponse(e.to_string()))
+    }
+
+    fn connection_error(&self, error: ureq::Error) -> ProviderError {
+        match error {
+            ureq::Error::Status(status, response) => ProviderError::Http {
+                status,
+                message: response.into_string().unwrap_or_default(),
+                retry_after: None,
+            },
+            ureq::Error::Transport(_) => self.unreachable(),
+        }
+    }
+
+    fn unreachable(&self) -> ProviderError {
+        ProviderError::Transport(format!(
+            "cannot connect to Ollama at {}. Start it with: ollama serve",
+            self.base_url
+        ))
+    }
+
+    fn body(&self, request: &ProviderRequest) -> Value {
+        let (system, turns) = request.conversation();
+        let mut messages = Vec::new();
+        if let Some(system) = system {
+            messages.push(json!({ "role": "system", "content": system }));
+        }
+        messages.extend(turns.iter().map(|turn| json!({ "role": turn.role, "content": turn.content })));
+
+        let mut body = json!({ "model": request.model, "messages": messages, "stream": false });
+        if !request.tools.is_empty() {
+            let tools: Vec<Value> = request
+                .tools
+                .iter()
+                .map(|tool| json!({ "type": "function", "function": tool }))
+                .collect();
+            body["tools"] = json!(tools);
+        }
+        // Ollama constrains the output to a schema given as `format`
+        if let Some(schema) = &request.response_schema {
+            body["format"] = schema.clone();
+        }
+
+        let mut options = Map::new();
+        if let Some(params) = &request.parameters {
+            insert_param(&mut options, "temperature", &params.temperature);
+            insert_param(&mut options, "num_predict", &params.max_tokens);
+            insert_param(&mut options, "top_p", &params.top_p);
+            insert_param(&mut options, "frequency_penalty", &params.frequency_penalty);
+            insert_param(&mut options, "presence_penalty", &params.presence_penalty);
+            insert_param(&mut options, "stop", &params.stop);
+        }
+        if !options.is_empty() {
+            body["options"] = Value::Object(options);
+        }
+        body
+    }
+
+    fn chat_url(&self, timeout: Duration) -> ureq::Request {
+        ureq::post(&format!("{}/api/chat", self.base_url)).timeout(timeout)
+    }
+
+    /// Swap a bare connection failure for the "is the daemon running" hint
+    fn hint_unreachable(&self, error: ProviderError) -> ProviderError {
+        match error {
+            ProviderError::Transport(_) => self.unreachable(),
+            other => other,
+        }
+    }
+}
+
+/// Ollama sends arguments as an object, not JSON-encoded like OpenAI
+fn tool_calls_from(message: &Value) -> Vec<ToolCall> {
+    message["tool_calls"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|call| ToolCall {
+            id: None,
+            name: call["function"]["name"].as_str().unwrap_or_default().to_string(),
+            arguments: call["function"]["arguments"].clone(),
+        })
+        .collect()
+}
+
+fn usage_from(data: &Value) -> TokenUsage {
+    TokenUsage {
+        input: data["prompt_eval_count"].as_i64(),
+        output: data["eval_count"].as_i64(),
+        ..Default::default()
+    }
+}
+
+impl Provider for OllamaProvider {
+    fn name(&self) -> &'static str {
+        "Ollama"
+    }
+
+    fn complete(&self, request: &ProviderRequest) -> Result<Completion, ProviderError> {
+        let data = send_json(self.chat_url(request.call_timeout()), &self.body(request)).map_err(|e| self.hint_unreachable(e))?;
+
+        Ok(Completion {
+            content: data["message"]["content"].as_str().unwrap_or_default().to_string(),
+            usage: usage_from(&data),
+            finish_reason: data["done_reason"].as_str().map(|s| s.to_string()),
+            tool_calls: tool_calls_from(&data["message"]),
+            ..Default::default()
+        })
+    }
+
+    /// Ollama streams newline-delimited JSON rather than SSE; the final
+    /// `"done": true` line carries the token counts
+    fn stream(
+        &self,
+        request: &ProviderRequest,
+        on_delta: &mut dyn FnMut(&str),
+    ) -> Result<Completion, ProviderError> {
+        let mut body = self.body(request);
+        body["stream"] = json!(true);
+        let response = send(self.chat_url(request.call_timeout()), &body).map_err(|e| self.hint_unreachable(e))?;
+
+        let mut completion = Completion::default();
+        for line in BufReader::new(response.into_reader()).lines() {
+            let line = line.map_err(|e| transport_error(&e))?;
+            cancel::check(request.cancel.as_ref())?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let chunk: Value =
+                serde_json::from_str(&line).map_err(|e| ProviderError::InvalidResponse(e.to_string()))?;
+            if let Some(error) = chunk["error"].as_str() {
+                return Err(ProviderError::Http {
+                    status: 500,
+                    message: error.to_string(),
+                    retry_after: None,
+                });
+            }
+            if let Some(text) = chunk["message"]["content"].as_str().filter(|t| !t.is_empty()) {
+                completion.content.push_str(text);
+                on_delta(text);
+            }
+            completion.tool_calls.extend(tool_calls_from(&chunk["message"]));
+            if chunk["done"] == true {
+                completion.usage = usage_from(&chunk);
+                completion.finish_reason = chunk["done_reason"].as_str().map(|s| s.to_string());
+                break;
+            }
+        }
+        Ok(completion)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parameters::AIParameters;
+    use crate::providers::test_server;
+
+    #[test]
+    fn test_base_url_from_host() {
+        assert_eq!(base_url_from_host(None), "http://localhost:11434");
+        assert_eq!(base_url_from_host(Some("0.0.0.0")), "http://0.0.0.0:11434");
+        assert_eq!(base_url_from_host(Some("gpu-box:8080")), "http://gpu-box:8080");
+        assert_eq!(base_url_from_host(Some("gpu-box/ollama/")), "http://gpu-box:11434/ollama");
+        // A scheme brings its own default port
+        assert_eq!(base_url_from_host(Some("https://ollama.internal/")), "https://ollama.internal");
+        assert_eq!(base_url_from_host(Some("http://gpu-box")), "http://gpu-box");
+        assert_eq!(base_url_from_host(Some("http://gpu-box:8080")), "http://gpu-box:8080");
+        // IPv6
+        assert_eq!(base_url_from_host(Some("[::1]")), "http://[::1]:11434");
+        assert_eq!(base_url_from_host(Some("[::1]:8080")), "http://[::1]:8080");
+        assert_eq!(base_url_from_host(Some("::1")), "http://[::1]:11434");
+        assert_eq!(base_url_from_host(Some("http://[::1]")), "http://[::1]");
+    }
+
+    #[test]
+    fn test_chat_against_local_server() {
+        let server = test_server::respond(
+            200,
+            r#"{"model":"llama3","message":{"role":"assistant","content":"Hey"},"done":true,
+                "prompt_eval_count":20,"eval_count":4}"#,
+        );
+        let provider = OllamaProvider::new(server.base_url(""));
+        let request = ProviderRequest {
+            model: "llama3".to_string(),
+            prompt: "Hi".to_string(),
+            parameters: Some(AIParameters { max_tokens: Some(32), ..Default::default() }),
+            ..Default::default()
+        };
+
+        let completion = provider.complete(&request).unwrap();
+        assert_eq!(completion.content, "Hey");
+        assert_eq!(completion.usage.total(), Some(24));
+
+        let captured = server.request();
+        assert_eq!(captured.path, "/api/chat");
+        assert_eq!(captured.body["stream"], false);
+        assert_eq!(captured.body["options"]["num_predict"], 32);
+    }
+
+    #[test]
+    fn test_streaming_chat() {
+        let server = test_server::serve(vec![test_server::CannedResponse {
+            status: 200,
+            headers: vec![("Content-Type".to_string(), "application/x-ndjson".to_string())],
+            body: [
+                r#"{"message":{"role":"assistant","content":"He"},"done":false}"#,
+                r#"{"message":{"role":"assistant","content":"y"},"done":false}"#,
+                r#"{"message":{"role":"assistant","content":""},"done":true,"prompt_eval_count":20,"eval_count":2}"#,
+            ]
+            .join("\n"),
+        }]);
+        let provider = OllamaProvider::new(server.base_url(""));
+        let request = ProviderRequest {
+            model: "llama3".to_string(),
+            prompt: "Hi".to_string(),
+            ..Default::default()
+        };
+
+        let mut deltas = Vec::new();
+        let completion = provider.stream(&request, &mut |t| deltas.push(t.to_string())).unwrap();
+        assert_eq!(deltas, vec!["He", "y"]);
+        assert_eq!(completion.content, "Hey");
+        assert_eq!(completion.usage.total(), Some(22));
+        assert_eq!(server.request().body["stream"], true);
+    }
+
+    #[test]
+    fn test_list_models() {
+        let server = test_server::respond(
+            200,
+            r#"{"models":[{"name":"llama3:latest","size":4661224676,
+                "details":{"family":"llama","parameter_size":"8B","quantization_level":"Q4_0"}}]}"#,
+        );
+        let provider = OllamaProvider::new(server.base_url(""));
+
+        let models = provider.list_models().unwrap();
+        assert_eq!(models.len(), 1);
+        assert_eq!(models[0].name, "llama3:latest");
+        assert_eq!(models[0].details.as_ref().unwrap().parameter_size.as_deref(), Some("8B"));
+        assert_eq!(server.request().path, "/api/tags");
+    }
+}
@@ -0,0 +1,470 @@
+//! OpenAI-compatible chat completions (`/v1/chat/completions`).
+//!
+//! Serves OpenAI itself plus anything speaking the same protocol: LM Studio,
+//! vLLM, llama.cpp server, or a local stand-in during tests.
+
+use super::{
+    insert_param, read_sse, send, send_json, CancelToken, Completion, Provider, ProviderError, ProviderRequest,
+    TokenUsage,
+};
+use crate::tools::{parse_arguments, ToolCall};
+use serde_json::{json, Map, Value};
+use std::time::Duration;
+
+pub const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+pub const LMSTUDIO_BASE_URL: &str = "http://localhost:1234/v1";
+
+pub struct OpenAiProvider {
+    name: &'static str,
+    base_url: String,
+    api_key: Option<String>,
+    headers: Vec<(&'static str, String)>,
+}
+
+impl OpenAiProvider {
+    pub fn new(api_key: String) -> Self {
+        Self::with_base_url("OpenAI", OPENAI_BASE_URL, Some(api_key))
+    }
+
+    /// Point the client at any compatible server. `base_url` includes the
+    /// version prefix (e.g. `http://localhost:8000/v1`); keyless servers get no
+    /// `Authorization` header.
+    pub fn with_base_url(name: &'static str, base_url: &str, api_key: Option<String>) -> Self {
+        Self {
+            name,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+            headers: Vec::new(),
+        }
+    }
+
+    /// Send an extra header with every request
+    pub fn with_header(mut self, name: &'static str, value: &str) -> Self {
+        self.headers.push((name, value.to_string()));
+        self
+    }
+
+    pub(super) fn body(&self, request: &ProviderRequest) -> Value {
+        let (system, turns) = request.conversation();
+        let mut messages = Vec::new();
+        if let Some(system) = system {
+            messages.push(json!({ "role": "system", "content": system }));
+        }
+        messages.extend(turns.iter().map(|turn| json!({ "role": turn.role, "content": turn.content })));
+
+        let mut body = Map::new();
+        body.insert("model".to_string(), json!(request.model));
+        body.insert("messages".to_string(), json!(messages));
+        if let Some(params) = &request.parameters {
+            insert_param(&mut body, "temperature", &params.temperature);
+            insert_param(&mut body, max_tokens_field(&request.model), &params.max_tokens);
+            insert_param(&mut body, "top_p", &params.top_p);
+            insert_param(&mut body, "frequency_penalty", &params.frequency_penalty);
+            insert_param(&mut body, "presence_penalty", &params.presence_penalty);
+            insert_param(&mut body, "stop", &params.stop);
+        }
+        if !request.tools.is_empty() {
+            let tools: Vec<Value> = request
+                .tools
+                .iter()
+                .map(|tool| json!({ "type": "function", "function": tool }))
+                .collect();
+            body.insert("tools".to_string(), json!(tools));
+        }
+        if let Some(schema) = &request.response_schema {
+            let format = json!({ "name": schema_name(schema), "schema": schema, "strict": is_strict(schema) });
+            body.insert("response_format".to_string(), json!({ "type": "json_schema", "json_schema": format }));
+        }
+        Value::Object(body)
+    }
+
+    fn http(&self, timeout: Duration) -> ureq::Request {
+        let mut http = ureq::post(&format!("{}/chat/completions", self.base_url)).timeout(timeout);
+        if let Some(key) = &self.api_key {
+            http = http.set("Authorization", &format!("Bearer {}", key));
+        }
+        for (name, value) in &self.headers {
+            http = http.set(name, value);
+        }
+        http
+    }
+
+    pub(super) fn post(&self, body: &Value, timeout: Duration) -> Result<Value, ProviderError> {
+        send_json(self.http(timeout), body)
+    }
+
+    /// Stream a completion over SSE. Returns the assembled completion plus the
+    /// final chunk, which carries usage (and gateway metadata like cost).
+    pub(super) fn post_stream(
+        &self,
+        body: &Value,
+        timeout: Duration,
+        cancel: Option<&CancelToken>,
+        on_delta: &mut dyn FnMut(&str),
+    ) -> Result<(Completion, Value), ProviderError> {
+        let mut body = body.clone();
+        body["stream"] = json!(true);
+        body["stream_options"] = json!({ "include_usage": true });
+
+        let response = send(self.http(timeout), &body)?;
+        let mut completion = Completion::default();
+        let mut last_chunk = Value::Null;
+        // Tool calls arrive in pieces keyed by index: (id, name, arguments so far)
+        let mut calls: Vec<(Option<String>, String, String)> = Vec::new();
+
+        read_sse(response, cancel, |_, chunk| {
+            // Errors after the stream started arrive as a chunk
+            if let Some(error) = chunk.get("error") {
+                return Err(ProviderError::Http {
+                    status: error["code"].as_u64().map(|c| c as u16).unwrap_or(500),
+                    message: error["message"].as_str().unwrap_or("stream error").to_string(),
+                    retry_after: None,
+                });
+            }
+            if let Some(text) = chunk["choices"][0]["delta"]["content"].as_str() {
+                if !text.is_empty() {
+                    completion.content.push_str(text);
+                    on_delta(text);
+                }
+            }
+            for delta in chunk["choices"][0]["delta"]["tool_calls"].as_array().into_iter().flatten() {
+                let index = delta["index"].as_u64().unwrap_or(0) as usize;
+                if calls.len() <= index {
+                    calls.resize(index + 1, (None, String::new(), String::new()));
+                }
+                let call = &mut calls[index];
+                if let Some(id) = delta["id"].as_str() {
+                    call.0 = Some(id.to_string());
+                }
+                call.1.push_str(delta["function"]["name"].as_str().unwrap_or_default());
+                call.2.push_str(delta["function"]["arguments"].as_str().unwrap_or_default());
+            }
+            if let Some(reason) = chunk["choices"][0]["finish_reason"].as_str() {
+                completion.finish_reason = Some(reason.to_string());
+            }
+            if chunk["usage"].is_object() {
+                completion.usage = usage_from(&chunk["usage"]);
+            }
+            last_chunk = chunk.clone();
+            Ok(())
+        })?;
+        completion.tool_calls = calls
+            .into_iter()
+            .map(|(id, name, arguments)| ToolCall { id, name, arguments: parse_arguments(&arguments) })
+            .collect();
+
+        Ok((completion, last_chunk))
+    }
+}
+
+impl Provider for OpenAiProvider {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn complete(&self, request: &ProviderRequest) -> Result<Completion, ProviderError> {
+        let data = self.post(&self.body(request), request.call_timeout())?;
+        completion_from(&data)
+    }
+
+    fn stream(
+        &self,
+        request: &ProviderRequest,
+        on_delta: &mut dyn FnMut(&str),
+    ) -> Result<Completion, ProviderError> {
+        let (completion, _) = self.post_stream(&self.body(request), request.call_timeout(), request.cancel.as_ref(), on_delta)?;
+        Ok(completion)
+    }
+}
+
+pub(super) fn completion_from(data: &Value) -> Result<Completion, ProviderError> {
+    let choice = data["choices"]
+        .get(0)
+        .ok_or_else(|| ProviderError::InvalidResponse("no choices returned".to_string()))?;
+
+    Ok(Completion {
+        content: choice["message"]["content"].as_str().unwrap_or_default().to_string(),
+        usage: usage_from(&data["usage"]),
+        finish_reason: choice["finish_reason"].as_str().map(|s| s.to_string()),
+        tool_calls: tool_calls_from(&choice["message"]["tool_calls"]),
+        ..Default::default()
+    })
+}
+
+/// Reasoning models (o1, o3-mini, gpt-5, ...) reject `max_tokens` and take
+/// `max_completion_tokens`; other servers speaking the protocol may only know
+/// `max_tokens`
+fn max_tokens_field(model: &str) -> &'static str {
+    let o_series = model.strip_prefix('o').is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit()));
+    if o_series || model.starts_with("gpt-5") {
+        "max_completion_tokens"
+    } else {
+        "max_tokens"
+    }
+}
+
+/// `tool_calls` of a chat completion message; arguments come JSON-encoded
+fn tool_calls_from(calls: &Value) -> Vec<ToolCall> {
+    calls
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|call| ToolCall {
+            id: call["id"].as_str().map(|s| s.to_string()),
+            name: call["function"]["name"].as_str().unwrap_or_default().to_string(),
+            arguments: parse_arguments(call["function"]["arguments"].as_str().unwrap_or_default()),
+        })
+        .collect()
+}
+
+/// `json_schema.name` must be `[a-zA-Z0-9_-]{1,64}`; use the schema's
+/// title when it fits
+fn schema_name(schema: &Value) -> String {
+    schema["title"]
+        .as_str()
+        .map(|title| title.replace(' ', "_"))
+        .filter(|name| {
+            (1..=64).contains(&name.len()) && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        })
+        .unwrap_or_else(|| "response".to_string())
+}
+
+/// Strict mode is only accepted for schemas whose objects all list every
+/// property as required and forbid additional ones; other schemas are sent
+/// as a non-binding hint
+fn is_strict(schema: &Value) -> bool {
+    let Some(map) = schema.as_object() else {
+        return true;
+    };
+    if schema["type"] == "object" || map.contains_key("properties") {
+        let properties = map.get("properties").and_then(Value::as_object);
+        let required: Vec<&str> = schema["required"].as_array().into_iter().flatten().filter_map(Value::as_str).collect();
+        let all_required = properties.into_iter().flatten().all(|(key, _)| required.contains(&key.as_str()));
+        if schema["additionalProperties"] != false || !all_required {
+            return false;
+        }
+    }
+    let children = ["properties", "$defs", "definitions"]
+        .iter()
+        .filter_map(|key| map.get(*key).and_then(Value::as_object))
+        .flat_map(|nested| nested.values())
+        .chain(map.get("items"))
+        .chain(map.get("anyOf").and_then(Value::as_array).into_iter().flatten());
+    children.into_iter().all(is_strict)
+}
+
+fn usage_from(usage: &Value) -> TokenUsage {
+    TokenUsage {
+        input: usage["prompt_tokens"].as_i64(),
+        output: usage["completion_tokens"].as_i64(),
+        cached: usage["prompt_tokens_details"]["cached_tokens"].as_i64(),
+        reasoning: usage["completion_tokens_details"]["reasoning_tokens"].as_i64(),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parameters::AIParameters;
+    use crate::providers::test_server;
+
+    fn request() -> ProviderRequest {
+        ProviderRequest {
+            model: "gpt-4o-mini".to_string(),
+            prompt: "Say hi".to_string(),
+            system_prompt: Some("Be brief".to_string()),
+            parameters: Some(AIParameters {
+                temperature: Some(0.2),
+                max_tokens: Some(64),
+                image_size: Some("1024x1024".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_chat_completion_against_local_server() {
+        let server = test_server::respond(
+            200,
+            r#"{"choices":[{"message":{"role":"assistant","content":"Hi!"},"finish_reason":"length"}],
+                "usage":{"prompt_tokens":9,"completion_tokens":3,"total_tokens":12,
+                         "prompt_tokens_details":{"cached_tokens":4},
+                         "completion_tokens_details":{"reasoning_tokens":1}}}"#,
+        );
+        let provider = OpenAiProvider::with_base_url("OpenAI", &server.base_url("/v1"), Some("sk-test".to_string()));
+
+        let completion = provider.complete(&request()).unwrap();
+        assert_eq!(completion.content, "Hi!");
+        assert_eq!(completion.usage.total(), Some(12));
+        assert_eq!(completion.usage.cached, Some(4));
+        assert_eq!(completion.usage.reasoning, Some(1));
+        assert_eq!(completion.finish_reason.as_deref(), Some("length"));
+        assert!(crate::providers::is_truncated("length"));
+
+        let captured = server.request();
+        assert_eq!(captured.method, "POST");
+        assert_eq!(captured.path, "/v1/chat/completions");
+        assert_eq!(captured.header("authorization"), Some("Bearer sk-test"));
+        assert_eq!(captured.body["messages"][0]["role"], "system");
+        assert_eq!(captured.body["messages"][1]["content"], "Say hi");
+        assert_eq!(captured.body["temperature"], 0.2);
+        assert_eq!(captured.body["max_tokens"], 64);
+        assert!(captured.body.get("imageSize").is_none());
+    }
+
+    #[test]
+    fn test_reasoning_models_get_max_completion_tokens() {
+        let provider = OpenAiProvider::with_base_url("OpenAI", "http://localhost", None);
+        for model in ["o1", "o3-mini", "o4-mini-2025-04-16", "gpt-5"] {
+            let body = provider.body(&ProviderRequest { model: model.to_string(), ..request() });
+            assert_eq!(body["max_completion_tokens"], 64, "{}", model);
+            assert!(body.get("max_tokens").is_none(), "{}", model);
+        }
+        let body = provider.body(&ProviderRequest { model: "omni-moderation".to_string(), ..request() });
+        assert_eq!(body["max_tokens"], 64);
+    }
+
+    #[test]
+    fn test_streaming_chat_completion() {
+        let server = test_server::serve(vec![test_server::CannedResponse {
+            status: 200,
+            headers: vec![("Content-Type".to_string(), "text/event-stream".to_string())],
+            body: [
+                r#"data: {"choices":[{"delta":{"role":"assistant"}}]}"#,
+                r#"data: {"choices":[{"delta":{"content":"Hel"}}]}"#,
+                r#"data: {"choices":[{"delta":{"content":"lo"}}]}"#,
+                r#"data: {"choices":[],"usage":{"prompt_tokens":4,"completion_tokens":2}}"#,
+                "data: [DONE]",
+            ]
+            .join("\n\n"),
+        }]);
+        let provider = OpenAiProvider::with_base_url("OpenAI", &server.base_url("/v1"), None);
+
+        let mut deltas = Vec::new();
+        let completion = provider
+            .stream(&request(), &mut |text| deltas.push(text.to_string()))
+            .unwrap();
+        assert_eq!(deltas, vec!["Hel", "lo"]);
+        assert_eq!(completion.content, "Hello");
+        assert_eq!(completion.usage.total(), Some(6));
+        assert_eq!(server.request().body["stream"], true);
+    }
+
+    #[test]
+    fn test_tool_calls_sent_and_parsed() {
+        let server = test_server::respond(
+            200,
+            r#"{"choices":[{"message":{"role":"assistant","content":null,"tool_calls":[
+                {"id":"call_1","type":"function","function":{"name":"get_weather","arguments":"{\"city\":\"Paris\"}"}}]},
+                "finish_reason":"tool_calls"}]}"#,
+        );
+        let provider = OpenAiProvider::with_base_url("OpenAI", &server.base_url("/v1"), None);
+        let request = ProviderRequest {
+            tools: vec![crate::tools::ToolDefinition {
+                name: "get_weather".to_string(),
+                description: Some("Current weather".to_string()),
+                parameters: json!({ "type": "object", "properties": { "city": { "type": "string" } } }),
+            }],
+            ..request()
+        };
+
+        let completion = provider.complete(&request).unwrap();
+        assert_eq!(completion.content, "");
+        assert_eq!(completion.tool_calls.len(), 1);
+        assert_eq!(completion.tool_calls[0].id.as_deref(), Some("call_1"));
+        assert_eq!(completion.tool_calls[0].arguments, json!({ "city": "Paris" }));
+
+        let captured = server.request();
+        assert_eq!(captured.body["tools"][0]["type"], "function");
+        assert_eq!(captured.body["tools"][0]["function"]["name"], "get_weather");
+        assert_eq!(captured.body["tools"][0]["function"]["parameters"]["type"], "object");
+    }
+
+    #[test]
+    fn test_streamed_tool_call_fragments() {
+        let server = test_server::serve(vec![test_server::CannedResponse {
+            status: 200,
+            headers: vec![("Content-Type".to_string(), "text/event-stream".to_string())],
+            body: [
+                r#"data: {"choices":[{"delta":{"tool_calls":[{"index":0,"id":"call_1","function":{"name":"get_weather","arguments":""}}]}}]}"#,
+                r#"data: {"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"city\":"}}]}}]}"#,
+                r#"data: {"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"\"Paris\"}"}}]},"finish_reason":"tool_calls"}]}"#,
+                "data: [DONE]",
+            ]
+            .join("\n\n"),
+        }]);
+        let provider = OpenAiProvider::with_base_url("OpenAI", &server.base_url("/v1"), None);
+
+        let completion = provider.stream(&request(), &mut |_| {}).unwrap();
+        assert_eq!(completion.tool_calls.len(), 1);
+        assert_eq!(completion.tool_calls[0].name, "get_weather");
+        assert_eq!(completion.tool_calls[0].arguments, json!({ "city": "Paris" }));
+        assert_eq!(completion.finish_reason.as_deref(), Some("tool_calls"));
+    }
+
+    #[test]
+    fn test_cancelled_stream_stops_reading() {
+        let server = test_server::serve(vec![test_server::CannedResponse {
+            status: 200,
+            headers: vec![("Content-Type".to_string(), "text/event-stream".to_string())],
+            body: [
+                r#"data: {"choices":[{"delta":{"content":"One"}}]}"#,
+                r#"data: {"choices":[{"delta":{"content":" two"}}]}"#,
+                "data: [DONE]",
+            ]
+            .join("\n\n"),
+        }]);
+        let provider = OpenAiProvider::with_base_url("OpenAI", &server.base_url("/v1"), None);
+        let cancel = CancelToken::default();
+        let request = ProviderRequest { cancel: Some(cancel.clone()), ..request() };
+
+        let mut deltas = Vec::new();
+        let result = provider.stream(&request, &mut |text| {
+            deltas.push(text.to_string());
+            cancel.cancel();
+        });
+        assert!(matches!(result, Err(ProviderError::Cancelled)));
+        assert_eq!(deltas, vec!["One"]);
+    }
+
+    #[test]
+    fn test_response_format_from_schema() {
+        let strict = json!({
+            "title": "Review summary",
+            "type": "object",
+            "properties": { "tags": { "type": "array", "items": { "type": "object", "properties": {}, "additionalProperties": false } } },
+            "required": ["tags"],
+            "additionalProperties": false
+        });
+        let provider = OpenAiProvider::with_base_url("OpenAI", "http://localhost/v1", None);
+        let body = provider.body(&ProviderRequest { response_schema: Some(strict.clone()), ..request() });
+        assert_eq!(body["response_format"]["type"], "json_schema");
+        assert_eq!(body["response_format"]["json_schema"]["name"], "Review_summary");
+        assert_eq!(body["response_format"]["json_schema"]["strict"], true);
+        assert_eq!(body["response_format"]["json_schema"]["schema"], strict);
+
+        // An optional property rules out strict mode
+        let loose = json!({ "type": "object", "properties": { "a": {}, "b": {} }, "required": ["a"], "additionalProperties": false });
+        let body = provider.body(&ProviderRequest { response_schema: Some(loose), ..request() });
+        assert_eq!(body["response_format"]["json_schema"]["name"], "response");
+        assert_eq!(body["response_format"]["json_schema"]["strict"], false);
+        assert!(provider.body(&request()).get("response_format").is_none());
+    }
+
+    #[test]
+    fn test_keyless_server_and_error_status() {
+        let server = test_server::respond(400, r#"{"error":{"message":"model not loaded"}}"#);
+        let provider = OpenAiProvider::with_base_url("LM Studio", &server.base_url("/v1/"), None);
+
+        match provider.complete(&request()) {
+            Err(ProviderError::Http { status, message, .. }) => {
+                assert_eq!(status, 400);
+                assert_eq!(message, "model not loaded");
+            }
+            other => panic!("expected HTTP error, got {:?}", other),
+        }
+        assert_eq!(server.request().header("authorization"), None);
+    }
+}
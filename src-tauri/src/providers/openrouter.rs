@@ -0,0 +1,120 @@
+//! OpenRouter, an OpenAI-compatible gateway that adds provider routing and
+//! reports what each call cost and which upstream provider served it.
+
+use super::openai::{completion_from, OpenAiProvider};
+use super::{Completion, Provider, ProviderError, ProviderRequest};
+use serde_json::{json, Value};
+
+pub const OPENROUTER_BASE_URL: &str = "https://openrouter.ai/api/v1";
+const APP_URL: &str = "https://evvl.ai";
+const APP_TITLE: &str = "Evvl";
+
+pub struct OpenRouterProvider {
+    inner: OpenAiProvider,
+}
+
+impl OpenRouterProvider {
+    pub fn with_base_url(base_url: &str, api_key: String) -> Self {
+        Self {
+            inner: OpenAiProvider::with_base_url("OpenRouter", base_url, Some(api_key))
+                .with_header("HTTP-Referer", APP_URL)
+                .with_header("X-Title", APP_TITLE),
+        }
+    }
+}
+
+impl OpenRouterProvider {
+    fn body(&self, request: &ProviderRequest) -> Value {
+        let mut body = self.inner.body(request);
+        // Routing preferences use OpenRouter's own shape, e.g.
+        // `"provider": { "order": ["anthropic"], "allow_fallbacks": false }`
+        if let Some(routing) = request
+            .parameters
+            .as_ref()
+            .and_then(|p| p.extra.get("provider"))
+            .filter(|v| v.is_object())
+        {
+            body["provider"] = routing.clone();
+        }
+        // Ask for the billed cost in the usage block
+        body["usage"] = json!({ "include": true });
+        body
+    }
+}
+
+impl Provider for OpenRouterProvider {
+    fn name(&self) -> &'static str {
+        "OpenRouter"
+    }
+
+    fn complete(&self, request: &ProviderRequest) -> Result<Completion, ProviderError> {
+        let data = self.inner.post(&self.body(request), request.call_timeout())?;
+        let mut completion = completion_from(&data)?;
+        add_routing_metadata(&mut completion, &data);
+        Ok(completion)
+    }
+
+    fn stream(
+        &self,
+        request: &ProviderRequest,
+        on_delta: &mut dyn FnMut(&str),
+    ) -> Result<Completion, ProviderError> {
+        let (mut completion, last_chunk) = self.inner.post_stream(
+            &self.body(request),
+            request.call_timeout(),
+            request.cancel.as_ref(),
+            on_delta,
+        )?;
+        add_routing_metadata(&mut completion, &last_chunk);
+        Ok(completion)
+    }
+}
+
+/// Cost and upstream provider, present on full responses and on the final
+/// (usage) chunk of a stream
+fn add_routing_metadata(completion: &mut Completion, data: &Value) {
+    completion.cost = data["usage"]["cost"].as_f64();
+    completion.upstream_provider = data["provider"].as_str().map(|s| s.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::test_server;
+
+    #[test]
+    fn test_routing_headers_and_cost() {
+        let server = test_server::respond(
+            200,
+            r#"{"provider":"Anthropic","choices":[{"message":{"content":"ok"}}],
+                "usage":{"prompt_tokens":5,"completion_tokens":1,"cost":0.00042}}"#,
+        );
+        let provider = OpenRouterProvider::with_base_url(&server.base_url("/api/v1"), "sk-or".to_string());
+        let request = ProviderRequest {
+            model: "anthropic/claude-3.5-sonnet".to_string(),
+            prompt: "Ping".to_string(),
+            parameters: Some(
+                serde_json::from_value(json!({
+                    "temperature": 0,
+                    "provider": { "order": ["anthropic", "aws-bedrock"], "allow_fallbacks": false }
+                }))
+                .unwrap(),
+            ),
+            ..Default::default()
+        };
+
+        let completion = provider.complete(&request).unwrap();
+        assert_eq!(completion.content, "ok");
+        assert_eq!(completion.cost, Some(0.00042));
+        assert_eq!(completion.upstream_provider.as_deref(), Some("Anthropic"));
+
+        let captured = server.request();
+        assert_eq!(captured.path, "/api/v1/chat/completions");
+        assert_eq!(captured.header("http-referer"), Some(APP_URL));
+        assert_eq!(captured.header("x-title"), Some(APP_TITLE));
+        assert_eq!(captured.header("authorization"), Some("Bearer sk-or"));
+        assert_eq!(captured.body["provider"]["order"][1], "aws-bedrock");
+        assert_eq!(captured.body["usage"]["include"], true);
+        assert_eq!(captured.body["temperature"], 0.0);
+    }
+}
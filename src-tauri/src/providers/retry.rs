@@ -0,0 +1,168 @@
+//! Retry policy for provider calls: exponential backoff with jitter for
+//! rate limits, server errors and dropped connections.
+
+use super::ProviderError;
+use std::time::{Duration, Instant};
+
+/// Attempts per call (the first try included) unless the model config says otherwise
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+/// Longest `Retry-After` we are willing to sleep for; a call asked to wait
+/// longer fails instead of retrying early
+pub const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled for every further retry
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Run deadline: no attempt starts, and no retry is waited for, past it
+    pub deadline: Option<Instant>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            deadline: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Default backoff with the attempt budget from a `ProjectModelConfig`
+    pub fn with_max_attempts(max_attempts: Option<u32>) -> Self {
+        Self {
+            max_attempts: max_attempts.unwrap_or(DEFAULT_MAX_ATTEMPTS).max(1),
+            ..Default::default()
+        }
+    }
+
+    /// How long to wait after failed attempt number `attempt` (1-based).
+    /// A server-provided `Retry-After` wins over the computed backoff;
+    /// `None` when it is longer than `MAX_RETRY_AFTER`.
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        if let Some(retry_after) = retry_after {
+            return (retry_after <= MAX_RETRY_AFTER).then_some(retry_after);
+        }
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        // Equal jitter: half fixed, half random, so parallel workers that hit
+        // the same rate limit don't retry in lockstep
+        let half = exponential / 2;
+        Some(half + half.mul_f64(random_fraction()))
+    }
+}
+
+/// `error` with a note that the server asked for a longer wait than
+/// `MAX_RETRY_AFTER`, for a call given up on for that reason
+pub fn retry_after_too_long(error: ProviderError) -> ProviderError {
+    match error {
+        ProviderError::Http { status, message, retry_after: Some(wait) } => ProviderError::Http {
+            status,
+            message: format!(
+                "{} (asked to retry after {}s, longer than the {}s limit)",
+                message,
+                wait.as_secs(),
+                MAX_RETRY_AFTER.as_secs()
+            ),
+            retry_after: Some(wait),
+        },
+        error => error,
+    }
+}
+
+/// Whether a failed call is worth repeating
+pub fn is_retryable(error: &ProviderError) -> bool {
+    match error {
+        ProviderError::Http { status, .. } => matches!(status, 408 | 409 | 429) || *status >= 500,
+        ProviderError::Transport(_) | ProviderError::Timeout(_) => true,
+        _ => false,
+    }
+}
+
+/// Parse a `Retry-After` header: either delta-seconds (a non-negative
+/// integer, RFC 9110) or an HTTP date. Anything else is no hint.
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let millis = (date.timestamp_millis() - chrono::Utc::now().timestamp_millis()).max(0);
+    Some(Duration::from_millis(millis as u64))
+}
+
+/// A number in [0, 1) without pulling in a RNG crate
+fn random_fraction() -> f64 {
+    (uuid::Uuid::new_v4().as_u128() % 1_000_000) as f64 / 1_000_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_grows_and_is_capped() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(300),
+            deadline: None,
+        };
+        let first = policy.delay(1, None).unwrap();
+        assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+        let second = policy.delay(2, None).unwrap();
+        assert!(second >= Duration::from_millis(100) && second <= Duration::from_millis(200));
+        let capped = policy.delay(10, None).unwrap();
+        assert!(capped >= Duration::from_millis(150) && capped <= Duration::from_millis(300));
+    }
+
+    #[test]
+    fn test_retry_after_beyond_the_limit_is_not_shortened() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay(1, Some(Duration::from_secs(2))), Some(Duration::from_secs(2)));
+        assert_eq!(policy.delay(1, Some(MAX_RETRY_AFTER)), Some(MAX_RETRY_AFTER));
+        assert_eq!(policy.delay(1, Some(Duration::from_secs(3600))), None);
+
+        let error = ProviderError::Http {
+            status: 429,
+            message: "slow down".to_string(),
+            retry_after: Some(Duration::from_secs(3600)),
+        };
+        assert_eq!(
+            retry_after_too_long(error).to_string(),
+            "API error (429): slow down (asked to retry after 3600s, longer than the 60s limit)"
+        );
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("3"), Some(Duration::from_secs(3)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon"), None);
+        // Not delta-seconds, and must not panic on the way to a Duration
+        assert_eq!(parse_retry_after("inf"), None);
+        assert_eq!(parse_retry_after("NaN"), None);
+        assert_eq!(parse_retry_after("1e30"), None);
+        assert_eq!(parse_retry_after("-1"), None);
+        assert_eq!(parse_retry_after("1.5"), None);
+        assert_eq!(parse_retry_after("99999999999999999999"), None);
+        assert_eq!(parse_retry_after("86400"), Some(Duration::from_secs(86400)));
+    }
+
+    #[test]
+    fn test_retryable_errors() {
+        let http = |status| ProviderError::Http { status, message: String::new(), retry_after: None };
+        assert!(is_retryable(&http(429)));
+        assert!(is_retryable(&http(503)));
+        assert!(!is_retryable(&http(400)));
+        assert!(!is_retryable(&http(401)));
+        assert!(is_retryable(&ProviderError::Transport("connection reset".to_string())));
+        assert!(!is_retryable(&ProviderError::MissingApiKey("OPENAI_API_KEY")));
+    }
+}
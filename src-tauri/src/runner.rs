@@ -0,0 +1,1048 @@
+//! Headless evaluation execution.
+//!
+//! Runs a prompt against a set of model configs (optionally once per dataset
+//! item) without the webview, producing an `EvaluationRun` that matches what
+//! the GUI stores in `evvl_evaluation_runs`.
+
+use crate::cache::ResponseCache;
+use crate::parameters::AIParameters;
+use crate::pricing::PricingRegistry;
+use crate::providers::{self, CancelToken, ProviderRequest, RetryPolicy};
+use crate::schema;
+use crate::template;
+use crate::tools::{self, ToolDefinition};
+use crate::{
+    AIOutput, ApiKeys, DataSetItem, EvaluationResult, EvaluationRun, ModelConfig, ProjectModelConfig,
+    PromptMessage,
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Cells in flight at once when `--concurrency` is not given
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+/// Finished cells between two checkpoints of a running run
+pub const CHECKPOINT_EVERY: usize = 5;
+
+/// Saves a run while it executes, so a process that dies mid-run leaves a
+/// `running` run with the results so far for `evvl runs resume`
+pub type Checkpoint = Box<dyn Fn(&EvaluationRun) + Send + Sync>;
+
+/// Everything needed to execute a run, resolved by the CLI handler
+pub struct RunPlan {
+    pub project_id: String,
+    pub prompt_id: String,
+    pub prompt_version_id: String,
+    pub prompt: String,
+    pub system_prompt: Option<String>,
+    /// Turns of a multi-turn prompt version, sent instead of `prompt`
+    pub messages: Vec<PromptMessage>,
+    /// Functions offered to the model, from the prompt version
+    pub tools: Vec<ToolDefinition>,
+    /// JSON Schema every output is validated against, from the prompt version
+    pub response_schema: Option<Value>,
+    /// Parameters saved on the prompt version
+    pub version_parameters: Option<AIParameters>,
+    /// Parameters given as CLI flags, overriding every other layer
+    pub cli_parameters: AIParameters,
+    pub model_configs: Vec<ProjectModelConfig>,
+    pub data_set_id: Option<String>,
+    pub items: Vec<DataSetItem>,
+    /// Maximum number of cells executing at the same time
+    pub concurrency: usize,
+    /// Prices for results whose provider doesn't report a cost
+    pub pricing: PricingRegistry,
+    /// Response cache, `None` with `--no-cache`
+    pub cache: Option<ResponseCache>,
+    /// `--deadline`: cells still running or waiting at this point fail
+    /// with a timeout
+    pub deadline: Option<Instant>,
+    /// Ctrl-C: cells not started yet are skipped, and in-flight calls are
+    /// stopped and left out of the run
+    pub cancel: CancelToken,
+    /// Called with the run (status `running`) before any cell executes,
+    /// then every `CHECKPOINT_EVERY` finished cells
+    pub checkpoint: Option<Checkpoint>,
+}
+
+/// Progress of a single cell, reported while a streamed run executes.
+/// Serialized as one NDJSON line per event for `--json`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum RunEvent {
+    Start {
+        #[serde(rename = "modelConfigId")]
+        model_config_id: String,
+        #[serde(rename = "dataSetItemId")]
+        data_set_item_id: Option<String>,
+        provider: String,
+        model: String,
+    },
+    Delta {
+        #[serde(rename = "modelConfigId")]
+        model_config_id: String,
+        #[serde(rename = "dataSetItemId")]
+        data_set_item_id: Option<String>,
+        text: String,
+    },
+    Done {
+        #[serde(rename = "modelConfigId")]
+        model_config_id: String,
+        #[serde(rename = "dataSetItemId")]
+        data_set_item_id: Option<String>,
+        tokens: Option<i64>,
+        #[serde(rename = "finishReason")]
+        finish_reason: Option<String>,
+        latency: i64,
+        /// Served from the response cache, no provider call made
+        cached: bool,
+    },
+    Error {
+        #[serde(rename = "modelConfigId")]
+        model_config_id: String,
+        #[serde(rename = "dataSetItemId")]
+        data_set_item_id: Option<String>,
+        error: String,
+        latency: i64,
+    },
+}
+
+/// Split a CLI model spec into (provider, model).
+/// Mirrors `parseCliRunConfig` in lib/cli-events.ts: an explicit
+/// `provider/model` prefix wins, otherwise the provider is inferred.
+pub fn parse_model_spec(spec: &str) -> (String, String) {
+    if let Some((provider, model)) = spec.split_once('/') {
+        return (provider.to_string(), model.to_string());
+    }
+
+    let lower = spec.to_lowercase();
+    let provider = if lower.contains("gpt") || lower.contains("o1") || lower.contains("davinci") {
+        "openai"
+    } else if lower.contains("claude") {
+        "anthropic"
+    } else if lower.contains("gemini") {
+        "gemini"
+    } else {
+        "openrouter"
+    };
+    (provider.to_string(), spec.to_string())
+}
+
+/// Check that every dataset item (or the bare prompt when there is no
+/// dataset) supplies all template variables used by the prompt, the system
+/// prompt and the conversation turns. The error names each offending item.
+pub fn check_variables(plan: &RunPlan) -> Result<(), String> {
+    let no_variables = HashMap::new();
+    let targets: Vec<(String, &HashMap<String, String>)> = if plan.items.is_empty() {
+        vec![("prompt (no dataset)".to_string(), &no_variables)]
+    } else {
+        plan.items
+            .iter()
+            .map(|item| {
+                let label = format!("item '{}'", item.name.as_deref().unwrap_or(&item.id));
+                (label, &item.variables)
+            })
+            .collect()
+    };
+
+    let mut problems = Vec::new();
+    for (label, variables) in targets {
+        let mut missing = Vec::new();
+        let templates = std::iter::once(&plan.prompt)
+            .chain(&plan.system_prompt)
+            .chain(plan.messages.iter().map(|m| &m.content));
+        for template in templates {
+            for name in template::render(template, variables).missing {
+                if !missing.contains(&name) {
+                    missing.push(name);
+                }
+            }
+        }
+        if !missing.is_empty() {
+            problems.push(format!("  {}: missing {}", label, missing.join(", ")));
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Template variables not provided:\n{}", problems.join("\n")))
+    }
+}
+
+/// A (model config × dataset item) pair; the item is `None` without a dataset
+type Cell<'a> = (&'a ProjectModelConfig, Option<&'a DataSetItem>);
+
+/// Every cell of the plan, model-major and item-minor
+fn plan_cells(plan: &RunPlan) -> Vec<Cell<'_>> {
+    plan.model_configs
+        .iter()
+        .flat_map(|config| {
+            let items: Vec<Option<&DataSetItem>> = if plan.items.is_empty() {
+                vec![None]
+            } else {
+                plan.items.iter().map(Some).collect()
+            };
+            items.into_iter().map(move |item| (config, item))
+        })
+        .collect()
+}
+
+/// Whether `result` belongs to `cell`
+fn is_result_of(result: &EvaluationResult, (config, item): Cell<'_>) -> bool {
+    result.model_config_id == config.id && result.data_set_item_id.as_deref() == item.map(|i| i.id.as_str())
+}
+
+/// Status of a run over `cells` once `results` came back
+fn run_status(cells: usize, results: &[EvaluationResult]) -> &'static str {
+    if results.len() < cells {
+        "cancelled"
+    } else if results.iter().any(|r| r.error.is_none()) {
+        "completed"
+    } else {
+        "failed"
+    }
+}
+
+/// Execute every (model config × item) cell on a bounded pool of worker
+/// threads and build the run. Results keep the model-major, item-minor order
+/// of the plan regardless of completion order. Provider failures are recorded
+/// on the individual result, never abort the run.
+/// With `on_event`, responses are streamed and progress is reported per cell.
+/// A cancelled run keeps the cells that finished and has status `cancelled`.
+pub fn execute_run(
+    plan: &RunPlan,
+    keys: &ApiKeys,
+    on_event: Option<&(dyn Fn(RunEvent) + Sync)>,
+) -> EvaluationRun {
+    let cells = plan_cells(plan);
+    let run = EvaluationRun {
+        id: uuid::Uuid::new_v4().to_string(),
+        project_id: plan.project_id.clone(),
+        prompt_id: plan.prompt_id.clone(),
+        prompt_version_id: plan.prompt_version_id.clone(),
+        model_config_ids: plan.model_configs.iter().map(|c| c.id.clone()).collect(),
+        data_set_id: plan.data_set_id.clone(),
+        status: "running".to_string(),
+        results: Vec::new(),
+        created_at: chrono::Utc::now().timestamp_millis(),
+        completed_at: None,
+    };
+
+    let checkpoint = plan.checkpoint.as_ref().map(|save| {
+        save(&run);
+        |slots: &[Option<EvaluationResult>]| {
+            save(&EvaluationRun { results: slots.iter().flatten().cloned().collect(), ..run.clone() })
+        }
+    });
+    let results: Vec<EvaluationResult> = execute_cells(plan, &cells, keys, on_event, checkpoint.as_ref().map(|c| c as _))
+        .into_iter()
+        .flatten()
+        .collect();
+
+    EvaluationRun {
+        status: run_status(cells.len(), &results).to_string(),
+        results,
+        completed_at: Some(chrono::Utc::now().timestamp_millis()),
+        ..run
+    }
+}
+
+/// Cells of the plan that `run` has no successful result for
+pub fn pending_cells<'a>(plan: &'a RunPlan, run: &EvaluationRun) -> Vec<Cell<'a>> {
+    plan_cells(plan)
+        .into_iter()
+        .filter(|&cell| !run.results.iter().any(|r| r.error.is_none() && is_result_of(r, cell)))
+        .collect()
+}
+
+/// Execute the pending cells of an interrupted `run` and merge them into it.
+/// A cell that fails again, or is cancelled again, keeps its previous result.
+/// Results for cells no longer in the plan (deleted dataset items) are kept
+/// after the others.
+pub fn resume_run(
+    plan: &RunPlan,
+    run: EvaluationRun,
+    keys: &ApiKeys,
+    on_event: Option<&(dyn Fn(RunEvent) + Sync)>,
+) -> EvaluationRun {
+    let cells = plan_cells(plan);
+    let pending = pending_cells(plan, &run);
+    let run = EvaluationRun { status: "running".to_string(), completed_at: None, ..run };
+
+    let checkpoint = plan.checkpoint.as_ref().map(|save| {
+        save(&run);
+        |slots: &[Option<EvaluationResult>]| {
+            let (results, _) = merge_results(&cells, run.results.clone(), slots.iter().flatten().cloned());
+            save(&EvaluationRun { results, ..run.clone() })
+        }
+    });
+    let fresh = execute_cells(plan, &pending, keys, on_event, checkpoint.as_ref().map(|c| c as _));
+    let (results, status) = merge_results(&cells, run.results.clone(), fresh.into_iter().flatten());
+
+    EvaluationRun {
+        results,
+        status: status.to_string(),
+        completed_at: Some(chrono::Utc::now().timestamp_millis()),
+        ..run
+    }
+}
+
+/// Merge the results of re-executed cells into a run's `previous` ones, in
+/// plan order, and the status of the merged run
+fn merge_results(
+    cells: &[Cell<'_>],
+    mut previous: Vec<EvaluationResult>,
+    fresh: impl IntoIterator<Item = EvaluationResult>,
+) -> (Vec<EvaluationResult>, &'static str) {
+    let mut fresh: HashMap<(String, Option<String>), EvaluationResult> = fresh
+        .into_iter()
+        .map(|result| ((result.model_config_id.clone(), result.data_set_item_id.clone()), result))
+        .collect();
+
+    let mut results = Vec::new();
+    for &cell in cells {
+        let old = previous.iter().position(|r| is_result_of(r, cell)).map(|index| previous.remove(index));
+        match (old, fresh.remove(&(cell.0.id.clone(), cell.1.map(|i| i.id.clone())))) {
+            (Some(old), _) if old.error.is_none() => results.push(old),
+            (_, Some(new)) => results.push(new),
+            (old, None) => results.extend(old),
+        }
+    }
+    let status = run_status(cells.len(), &results);
+    results.append(&mut previous);
+    (results, status)
+}
+
+/// Receives the result slots of `execute_cells` as cells finish
+type SlotsCheckpoint<'a> = &'a (dyn Fn(&[Option<EvaluationResult>]) + Sync);
+
+/// Run `cells` on the worker pool; a slot stays `None` for a cell the run
+/// was cancelled before finishing. `checkpoint` gets the slots every
+/// `CHECKPOINT_EVERY` finished cells.
+fn execute_cells(
+    plan: &RunPlan,
+    cells: &[Cell<'_>],
+    keys: &ApiKeys,
+    on_event: Option<&(dyn Fn(RunEvent) + Sync)>,
+    checkpoint: Option<SlotsCheckpoint<'_>>,
+) -> Vec<Option<EvaluationResult>> {
+    let slots: Mutex<Vec<Option<EvaluationResult>>> = Mutex::new(vec![None; cells.len()]);
+    let next = AtomicUsize::new(0);
+    let finished = AtomicUsize::new(0);
+    let workers = plan.concurrency.clamp(1, cells.len().max(1));
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                if plan.cancel.is_cancelled() {
+                    break;
+                }
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(&(config, item)) = cells.get(index) else {
+                    break;
+                };
+                let result = execute_cell(plan, config, item, keys, on_event);
+                let mut slots = slots.lock().unwrap();
+                slots[index] = result;
+                let finished = finished.fetch_add(1, Ordering::Relaxed) + 1;
+                if let Some(checkpoint) = checkpoint.filter(|_| finished % CHECKPOINT_EVERY == 0) {
+                    // Under the lock, so checkpoints are saved in order
+                    checkpoint(&slots);
+                }
+            });
+        }
+    });
+
+    slots.into_inner().unwrap()
+}
+
+/// Run one cell and shape it like the TypeScript `EvaluationResult`;
+/// `None` when the run was cancelled before the cell finished
+fn execute_cell(
+    plan: &RunPlan,
+    config: &ProjectModelConfig,
+    item: Option<&DataSetItem>,
+    keys: &ApiKeys,
+    on_event: Option<&(dyn Fn(RunEvent) + Sync)>,
+) -> Option<EvaluationResult> {
+    let no_variables = HashMap::new();
+    let variables = item.map(|i| &i.variables).unwrap_or(&no_variables);
+    let prompt = template::render(&plan.prompt, variables).text;
+    let system_prompt = plan
+        .system_prompt
+        .as_ref()
+        .map(|system| template::render(system, variables).text);
+    let messages = plan
+        .messages
+        .iter()
+        .map(|message| PromptMessage {
+            role: message.role,
+            content: template::render(&message.content, variables).text,
+        })
+        .collect();
+    let parameters = AIParameters::merged(
+        &config.provider,
+        plan.version_parameters.as_ref(),
+        config.parameters.as_ref(),
+        &plan.cli_parameters,
+    );
+
+    let request = ProviderRequest {
+        model: config.model.clone(),
+        prompt,
+        system_prompt,
+        parameters: Some(parameters.clone()),
+        messages,
+        tools: plan.tools.clone(),
+        response_schema: plan.response_schema.clone(),
+        timeout: config.timeout_ms.map(Duration::from_millis),
+        // Attached by `generate_cancellable`
+        cancel: None,
+    };
+    let item_id = item.map(|i| i.id.clone());
+    let retry = RetryPolicy {
+        deadline: plan.deadline,
+        ..RetryPolicy::with_max_attempts(config.max_attempts)
+    };
+
+    // Mock answers come from fixture files the key doesn't cover, and cost nothing
+    let cache = plan.cache.as_ref().filter(|_| config.provider != "mock");
+    let cache_key = cache.map(|_| ResponseCache::key(&config.provider, &request));
+    let hit = match (cache, &cache_key) {
+        (Some(cache), Some(key)) => cache.get(key),
+        _ => None,
+    };
+    let cached = hit.is_some();
+
+    let response = match on_event {
+        Some(emit) => {
+            emit(RunEvent::Start {
+                model_config_id: config.id.clone(),
+                data_set_item_id: item_id.clone(),
+                provider: config.provider.clone(),
+                model: config.model.clone(),
+            });
+            let response = match hit {
+                // Replay a hit as a single chunk so streamed consumers see the content
+                Some(hit) => {
+                    emit(RunEvent::Delta {
+                        model_config_id: config.id.clone(),
+                        data_set_item_id: item_id.clone(),
+                        text: hit.content.clone(),
+                    });
+                    hit
+                }
+                None => {
+                    let mut on_delta = |text: &str| {
+                        emit(RunEvent::Delta {
+                            model_config_id: config.id.clone(),
+                            data_set_item_id: item_id.clone(),
+                            text: text.to_string(),
+                        })
+                    };
+                    providers::generate_cancellable(
+                        &config.provider,
+                        keys,
+                        &request,
+                        &retry,
+                        &plan.cancel,
+                        Some(&mut on_delta),
+                    )
+                }
+            };
+            emit(match &response.error {
+                Some(error) => RunEvent::Error {
+                    model_config_id: config.id.clone(),
+                    data_set_item_id: item_id.clone(),
+                    error: error.clone(),
+                    latency: response.latency,
+                },
+                None => RunEvent::Done {
+                    model_config_id: config.id.clone(),
+                    data_set_item_id: item_id.clone(),
+                    tokens: response.tokens,
+                    finish_reason: response.finish_reason.clone(),
+                    latency: response.latency,
+                    cached,
+                },
+            });
+            response
+        }
+        None => hit.unwrap_or_else(|| {
+            providers::generate_cancellable(&config.provider, keys, &request, &retry, &plan.cancel, None)
+        }),
+    };
+    if response.error_details.as_ref().is_some_and(|d| d.kind == "cancelled") {
+        return None;
+    }
+
+    if let (Some(cache), Some(key), false) = (cache, &cache_key, cached) {
+        cache.put(key, &response);
+    }
+
+    let succeeded = response.error.is_none();
+    let schema_errors = match &plan.response_schema {
+        Some(response_schema) if succeeded => Some(schema::check_output(response_schema, &response.content)),
+        _ => None,
+    };
+    let mut output = AIOutput {
+        id: uuid::Uuid::new_v4().to_string(),
+        model_config: ModelConfig {
+            provider: config.provider.clone(),
+            model: config.model.clone(),
+            label: config.name.clone(),
+        },
+        output_type: "text".to_string(),
+        content: response.content,
+        image_url: None,
+        image_data: None,
+        error: response.error.clone(),
+        error_details: response.error_details,
+        tokens: response.tokens,
+        input_tokens: response.input_tokens,
+        output_tokens: response.output_tokens,
+        cached_tokens: response.cached_tokens,
+        cache_write_tokens: response.cache_write_tokens,
+        reasoning_tokens: response.reasoning_tokens,
+        finish_reason: response.finish_reason.clone(),
+        latency: succeeded.then_some(response.latency),
+        cost: response.cost,
+        upstream_provider: response.upstream_provider,
+        tool_calls: (!response.tool_calls.is_empty()).then(|| response.tool_calls.clone()),
+        // A cache hit made no calls; latency and tokens are the original ones
+        attempts: Some(if cached { 0 } else { response.attempts }),
+        cached: cached.then_some(true),
+        parameters: Some(parameters),
+        timestamp: chrono::Utc::now().timestamp_millis(),
+    };
+    if cached {
+        output.cost = Some(0.0);
+    } else if succeeded {
+        output.cost = plan.pricing.output_cost(&output);
+    }
+
+    // A failed call made no choice to grade
+    let assertions = match item.and_then(|i| i.expected_tool_calls.as_ref()) {
+        Some(expected) if succeeded => tools::check_tool_calls(expected, &response.tool_calls),
+        _ => Vec::new(),
+    };
+
+    Some(EvaluationResult {
+        id: uuid::Uuid::new_v4().to_string(),
+        model_config_id: config.id.clone(),
+        data_set_item_id: item_id,
+        output,
+        rating: None,
+        error: response.error,
+        assertions,
+        schema_errors,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_model_spec_with_provider() {
+        assert_eq!(
+            parse_model_spec("openai/gpt-4"),
+            ("openai".to_string(), "gpt-4".to_string())
+        );
+        // OpenRouter model IDs keep their own slash
+        assert_eq!(
+            parse_model_spec("openrouter/anthropic/claude-3"),
+            ("openrouter".to_string(), "anthropic/claude-3".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_model_spec_infers_provider() {
+        assert_eq!(parse_model_spec("gpt-4").0, "openai");
+        assert_eq!(parse_model_spec("claude-3-5-sonnet").0, "anthropic");
+        assert_eq!(parse_model_spec("gemini-pro").0, "gemini");
+        assert_eq!(parse_model_spec("mistral-large").0, "openrouter");
+    }
+
+    #[test]
+    fn test_check_variables_names_items() {
+        let item = |id: &str, name: Option<&str>, pairs: &[(&str, &str)]| DataSetItem {
+            id: id.to_string(),
+            name: name.map(|n| n.to_string()),
+            variables: pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            expected_tool_calls: None,
+        };
+        let plan = RunPlan {
+            prompt: "Review {{review}} in a {{tone|neutral}} tone".to_string(),
+            system_prompt: Some("You answer in {{language}}".to_string()),
+            data_set_id: Some("ds-1".to_string()),
+            items: vec![
+                item("item-1", Some("Happy customer"), &[("review", "Great"), ("language", "English")]),
+                item("item-2", Some("Angry customer"), &[("review", "Awful")]),
+                item("item-3", None, &[("language", "French")]),
+            ],
+            ..plan()
+        };
+
+        let error = check_variables(&plan).unwrap_err();
+        assert!(!error.contains("Happy customer"));
+        assert!(error.contains("item 'Angry customer': missing language"));
+        assert!(error.contains("item 'item-3': missing review"));
+    }
+
+    fn config(id: &str) -> ProjectModelConfig {
+        ProjectModelConfig {
+            id: id.to_string(),
+            project_id: "proj-1".to_string(),
+            name: id.to_string(),
+            provider: "nope".to_string(),
+            model: "x".to_string(),
+            parameters: None,
+            max_attempts: None,
+            timeout_ms: None,
+            created_at: 0,
+        }
+    }
+
+    /// A model config answered by the mock provider; `spec` is its model
+    /// string (`echo?latency=500`, `fixture:<path>`)
+    fn mock(id: &str, spec: &str) -> ProjectModelConfig {
+        ProjectModelConfig {
+            provider: "mock".to_string(),
+            model: spec.to_string(),
+            ..config(id)
+        }
+    }
+
+    fn item(id: &str, variables: &[(&str, &str)]) -> DataSetItem {
+        DataSetItem {
+            id: id.to_string(),
+            name: None,
+            variables: variables.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            expected_tool_calls: None,
+        }
+    }
+
+    /// Write a mock fixture to a temp file; returns its path and the mock spec
+    /// reading it
+    fn fixture(contents: &str) -> (std::path::PathBuf, String) {
+        let path = std::env::temp_dir().join(format!("evvl-fixture-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, contents).unwrap();
+        let spec = format!("fixture:{}", path.display());
+        (path, spec)
+    }
+
+    /// A plan with no models or items; tests override what they exercise
+    fn plan() -> RunPlan {
+        RunPlan {
+            project_id: "proj-1".to_string(),
+            prompt_id: "prompt-1".to_string(),
+            prompt_version_id: "ver-1".to_string(),
+            prompt: "Hello".to_string(),
+            system_prompt: None,
+            messages: Vec::new(),
+            tools: Vec::new(),
+            response_schema: None,
+            version_parameters: None,
+            cli_parameters: AIParameters::default(),
+            model_configs: vec![],
+            data_set_id: None,
+            items: vec![],
+            concurrency: DEFAULT_CONCURRENCY,
+            pricing: PricingRegistry::default(),
+            cache: None,
+            deadline: None,
+            cancel: CancelToken::default(),
+            checkpoint: None,
+        }
+    }
+
+    /// Run `plan` with the mock `spec` as its only model (`config-1`), its
+    /// items (if any) as dataset `ds-1`
+    fn run_against_mock(spec: &str, plan: RunPlan) -> EvaluationRun {
+        let plan = RunPlan {
+            model_configs: vec![mock("config-1", spec)],
+            data_set_id: (!plan.items.is_empty()).then(|| "ds-1".to_string()),
+            ..plan
+        };
+        execute_run(&plan, &ApiKeys::default(), None)
+    }
+
+    #[test]
+    fn test_results_keep_plan_order_under_concurrency() {
+        let plan = RunPlan {
+            model_configs: vec![config("a"), config("b"), config("c")],
+            data_set_id: Some("ds-1".to_string()),
+            items: (0..5).map(|i| item(&format!("item-{}", i), &[])).collect(),
+            concurrency: 8,
+            ..plan()
+        };
+        let keys = ApiKeys::default();
+
+        let run = execute_run(&plan, &keys, None);
+        let order: Vec<(String, String)> = run
+            .results
+            .iter()
+            .map(|r| (r.model_config_id.clone(), r.data_set_item_id.clone().unwrap()))
+            .collect();
+        let expected: Vec<(String, String)> = ["a", "b", "c"]
+            .iter()
+            .flat_map(|c| (0..5).map(move |i| (c.to_string(), format!("item-{}", i))))
+            .collect();
+        assert_eq!(order, expected);
+    }
+
+    #[test]
+    fn test_merged_parameters_recorded_on_result() {
+        let mut model_config = config("config-1");
+        model_config.parameters = Some(AIParameters { temperature: Some(0.9), top_p: Some(0.5), ..Default::default() });
+        let plan = RunPlan {
+            version_parameters: Some(AIParameters { max_tokens: Some(100), ..Default::default() }),
+            cli_parameters: AIParameters { temperature: Some(0.1), ..Default::default() },
+            model_configs: vec![model_config],
+            ..plan()
+        };
+        let keys = ApiKeys::default();
+
+        let run = execute_run(&plan, &keys, None);
+        let recorded = run.results[0].output.parameters.as_ref().unwrap();
+        assert_eq!(recorded.temperature, Some(0.1));
+        assert_eq!(recorded.top_p, Some(0.5));
+        assert_eq!(recorded.max_tokens, Some(100));
+    }
+
+    #[test]
+    fn test_unsupported_provider_recorded_as_error() {
+        let plan = RunPlan {
+            model_configs: vec![config("config-1")],
+            ..plan()
+        };
+        let keys = ApiKeys::default();
+
+        let events = std::sync::Mutex::new(Vec::new());
+        let run = execute_run(&plan, &keys, Some(&|event| events.lock().unwrap().push(event)));
+        assert_eq!(run.status, "failed");
+        assert_eq!(run.results.len(), 1);
+        assert_eq!(run.results[0].model_config_id, "config-1");
+        assert!(run.results[0].error.as_deref().unwrap().contains("not supported"));
+
+        let events = events.into_inner().unwrap();
+        assert_eq!(events.len(), 2);
+        let last = serde_json::to_value(&events[1]).unwrap();
+        assert_eq!(last["event"], "error");
+        assert_eq!(last["modelConfigId"], "config-1");
+    }
+
+    #[test]
+    fn test_cache_hit_skips_provider() {
+        let dir = std::env::temp_dir().join(format!("evvl-runner-cache-{}", uuid::Uuid::new_v4()));
+        let request = ProviderRequest {
+            model: "x".to_string(),
+            prompt: "Hello".to_string(),
+            parameters: Some(AIParameters::default()),
+            ..Default::default()
+        };
+        let cached = providers::ProviderResponse {
+            content: "Hi from cache".to_string(),
+            tokens: Some(5),
+            input_tokens: Some(2),
+            output_tokens: Some(3),
+            cached_tokens: None,
+            cache_write_tokens: None,
+            reasoning_tokens: None,
+            finish_reason: Some("stop".to_string()),
+            cost: None,
+            upstream_provider: None,
+            tool_calls: Vec::new(),
+            latency: 900,
+            attempts: 1,
+            error: None,
+            error_details: None,
+        };
+        ResponseCache::new(dir.clone(), true).put(&ResponseCache::key("nope", &request), &cached);
+
+        let plan = RunPlan {
+            model_configs: vec![config("config-1")],
+            cache: Some(ResponseCache::new(dir.clone(), true)),
+            ..plan()
+        };
+        let keys = ApiKeys::default();
+
+        let events = std::sync::Mutex::new(Vec::new());
+        let run = execute_run(&plan, &keys, Some(&|event| events.lock().unwrap().push(event)));
+        assert_eq!(run.status, "completed");
+        let output = &run.results[0].output;
+        assert_eq!(output.content, "Hi from cache");
+        assert_eq!(output.cached, Some(true));
+        assert_eq!(output.cost, Some(0.0));
+        assert_eq!(output.attempts, Some(0));
+        let done = serde_json::to_value(&events.into_inner().unwrap()[2]).unwrap();
+        assert_eq!(done["cached"], true);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_mock_responses_are_not_cached() {
+        let dir = std::env::temp_dir().join(format!("evvl-runner-cache-{}", uuid::Uuid::new_v4()));
+        let (path, spec) = fixture("first answer");
+        let cached_run = || {
+            let plan = RunPlan { cache: Some(ResponseCache::new(dir.clone(), true)), ..plan() };
+            run_against_mock(&spec, plan)
+        };
+
+        assert_eq!(cached_run().results[0].output.content, "first answer");
+        // Same request, edited fixture
+        std::fs::write(&path, "second answer").unwrap();
+        let run = cached_run();
+        let output = &run.results[0].output;
+        assert_eq!(output.content, "second answer");
+        assert_eq!(output.cached, None);
+        assert!(!dir.exists());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_mock_provider_echoes_rendered_prompt() {
+        let plan = RunPlan {
+            prompt: "Classify: {{review}}".to_string(),
+            items: vec![item("item-1", &[("review", "Great value")])],
+            ..plan()
+        };
+
+        let run = run_against_mock("echo", plan);
+        assert_eq!(run.status, "completed");
+        let output = &run.results[0].output;
+        assert_eq!(output.content, "Classify: Great value");
+        assert_eq!(output.output_tokens, Some(3));
+        assert_eq!(output.cost, Some(0.0));
+    }
+
+    #[test]
+    fn test_request_timeout_recorded_as_timeout() {
+        let model_config = ProjectModelConfig {
+            timeout_ms: Some(20),
+            max_attempts: Some(1),
+            ..mock("config-1", "echo?latency=2000")
+        };
+        let plan = RunPlan {
+            model_configs: vec![model_config],
+            ..plan()
+        };
+        let keys = ApiKeys::default();
+
+        let started = Instant::now();
+        let run = execute_run(&plan, &keys, None);
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(run.status, "failed");
+        let output = &run.results[0].output;
+        assert_eq!(output.error_details.as_ref().unwrap().kind, "timeout");
+        assert!(output.error.as_deref().unwrap().contains("no response within 20ms"));
+    }
+
+    #[test]
+    fn test_cancel_keeps_finished_cells() {
+        let plan = RunPlan {
+            model_configs: vec![mock("config-fast", "echo"), mock("config-slow", "echo?latency=5000")],
+            concurrency: 2,
+            ..plan()
+        };
+        let keys = ApiKeys::default();
+        let cancel = plan.cancel.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            cancel.cancel();
+        });
+
+        let started = Instant::now();
+        let run = execute_run(&plan, &keys, None);
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(run.status, "cancelled");
+        assert_eq!(run.results.len(), 1);
+        assert_eq!(run.results[0].model_config_id, "config-fast");
+        assert_eq!(run.model_config_ids, vec!["config-fast", "config-slow"]);
+    }
+
+    #[test]
+    fn test_resume_runs_only_missing_and_failed_cells() {
+        let flaky = ProjectModelConfig { max_attempts: Some(1), ..mock("config-flaky", "echo?error=503") };
+        let mut plan = RunPlan {
+            model_configs: vec![mock("config-good", "echo"), flaky],
+            data_set_id: Some("ds-1".to_string()),
+            items: vec![item("item-1", &[]), item("item-2", &[])],
+            ..plan()
+        };
+        let keys = ApiKeys::default();
+
+        let mut run = execute_run(&plan, &keys, None);
+        assert_eq!(run.results.iter().filter(|r| r.error.is_some()).count(), 2);
+        // The machine went to sleep before the second item of the good model finished
+        let kept_id = run.results[0].id.clone();
+        run.results.remove(1);
+        run.status = "running".to_string();
+        assert_eq!(pending_cells(&plan, &run).len(), 3);
+
+        plan.model_configs[1].model = "echo".to_string();
+        let run_id = run.id.clone();
+        let resumed = resume_run(&plan, run, &keys, None);
+        assert_eq!(resumed.id, run_id);
+        assert_eq!(resumed.status, "completed");
+        assert_eq!(resumed.results.len(), 4);
+        assert!(resumed.results.iter().all(|r| r.error.is_none()));
+        assert_eq!(resumed.results[0].id, kept_id);
+        let order: Vec<(&str, Option<&str>)> = resumed
+            .results
+            .iter()
+            .map(|r| (r.model_config_id.as_str(), r.data_set_item_id.as_deref()))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                ("config-good", Some("item-1")),
+                ("config-good", Some("item-2")),
+                ("config-flaky", Some("item-1")),
+                ("config-flaky", Some("item-2")),
+            ]
+        );
+        assert!(pending_cells(&plan, &resumed).is_empty());
+    }
+
+    #[test]
+    fn test_checkpoints_leave_a_resumable_running_run() {
+        let saved = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let sink = saved.clone();
+        let mut plan = RunPlan {
+            model_configs: vec![mock("config-1", "echo")],
+            data_set_id: Some("ds-1".to_string()),
+            items: (0..7).map(|i| item(&format!("item-{}", i), &[])).collect(),
+            concurrency: 1,
+            checkpoint: Some(Box::new(move |run: &EvaluationRun| sink.lock().unwrap().push(run.clone()))),
+            ..plan()
+        };
+        let keys = ApiKeys::default();
+
+        let run = execute_run(&plan, &keys, None);
+        assert_eq!(run.status, "completed");
+        let saved = saved.lock().unwrap().clone();
+        let progress: Vec<(&str, usize)> = saved.iter().map(|r| (r.status.as_str(), r.results.len())).collect();
+        assert_eq!(progress, vec![("running", 0), ("running", CHECKPOINT_EVERY)]);
+        assert!(saved.iter().all(|r| r.id == run.id && r.completed_at.is_none()));
+
+        // The process died after the last checkpoint: resume from what was saved
+        plan.checkpoint = None;
+        let partial = saved[1].clone();
+        assert_eq!(pending_cells(&plan, &partial).len(), 7 - CHECKPOINT_EVERY);
+        let resumed = resume_run(&plan, partial, &keys, None);
+        assert_eq!(resumed.status, "completed");
+        assert_eq!(resumed.results.len(), 7);
+        let kept: Vec<&str> = resumed.results[..CHECKPOINT_EVERY].iter().map(|r| r.id.as_str()).collect();
+        let checkpointed: Vec<&str> = saved[1].results.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(kept, checkpointed);
+    }
+
+    #[test]
+    fn test_conversation_turns_rendered_per_item() {
+        use crate::MessageRole;
+        let turn = |role, content: &str| PromptMessage { role, content: content.to_string() };
+        let mut plan = RunPlan {
+            prompt: "Is {{product}} in stock?".to_string(),
+            messages: vec![
+                turn(MessageRole::System, "You work at {{shop}}"),
+                turn(MessageRole::User, "Hi"),
+                turn(MessageRole::Assistant, "Hello! How can I help?"),
+                turn(MessageRole::User, "Is {{product}} in stock?"),
+            ],
+            items: vec![item("item-1", &[("product", "the red kettle")])],
+            ..plan()
+        };
+        assert_eq!(check_variables(&plan).unwrap_err(), "Template variables not provided:\n  item 'item-1': missing shop");
+
+        plan.items[0].variables.insert("shop".to_string(), "Kettle Co".to_string());
+        let run = run_against_mock("echo", plan);
+        let output = &run.results[0].output;
+        assert_eq!(output.content, "Is the red kettle in stock?");
+        // Every turn counts towards the input: 5 + 1 + 5 + 6 words
+        assert_eq!(output.input_tokens, Some(17));
+    }
+
+    #[test]
+    fn test_tool_calls_graded_per_item() {
+        use crate::tools::ExpectedToolCall;
+        let (path, spec) = fixture(
+            r#"{"responses": [
+                {"match": "Paris", "toolCalls": [{"name": "get_weather", "arguments": {"city": "Paris"}}]},
+                {"content": "I can't check that"}
+            ]}"#,
+        );
+        let question = |id: &str, question: &str, expected: Vec<ExpectedToolCall>| DataSetItem {
+            expected_tool_calls: Some(expected),
+            ..item(id, &[("question", question)])
+        };
+        let weather_in = |city: &str| ExpectedToolCall {
+            name: "get_weather".to_string(),
+            arguments: Some(serde_json::json!({ "city": city })),
+        };
+        let plan = RunPlan {
+            prompt: "{{question}}".to_string(),
+            tools: vec![ToolDefinition {
+                name: "get_weather".to_string(),
+                description: None,
+                parameters: serde_json::json!({ "type": "object" }),
+            }],
+            items: vec![
+                question("paris", "Weather in Paris?", vec![weather_in("Paris")]),
+                question("lyon", "Weather in Lyon?", vec![weather_in("Lyon")]),
+                question("hello", "Hello", Vec::new()),
+            ],
+            concurrency: 1,
+            ..plan()
+        };
+        let run = run_against_mock(&spec, plan);
+        let _ = std::fs::remove_file(path);
+
+        let passed = |index: usize| run.results[index].assertions.iter().map(|a| a.passed).collect::<Vec<_>>();
+        assert_eq!(run.results[0].output.tool_calls.as_ref().unwrap()[0].name, "get_weather");
+        assert_eq!(passed(0), vec![true, true]);
+        assert!(run.results[1].output.tool_calls.is_none());
+        assert_eq!(passed(1), vec![false]);
+        assert_eq!(run.results[2].assertions[0].kind, "no_tool_call");
+        assert_eq!(passed(2), vec![true]);
+    }
+
+    #[test]
+    fn test_outputs_validated_against_schema() {
+        let (path, spec) = fixture(
+            r#"{"responses": [
+                {"match": "good", "content": "{\"score\": 4}"},
+                {"match": "fenced", "content": "```json\n{\"score\": 9}\n```"},
+                {"content": "Four out of five"}
+            ]}"#,
+        );
+        let review = |id: &str| item(id, &[("review", id)]);
+        let plan = RunPlan {
+            prompt: "Score this review: {{review}}".to_string(),
+            response_schema: Some(serde_json::json!({
+                "type": "object",
+                "properties": { "score": { "type": "integer", "maximum": 5 } },
+                "required": ["score"]
+            })),
+            items: vec![review("good"), review("fenced"), review("rambling")],
+            concurrency: 1,
+            ..plan()
+        };
+        let run = run_against_mock(&spec, plan);
+        let _ = std::fs::remove_file(path);
+
+        let errors: Vec<Vec<String>> = run.results.iter().map(|r| r.schema_errors.clone().unwrap()).collect();
+        assert!(errors[0].is_empty());
+        assert_eq!(errors[1], vec!["$.score: 9 is greater than 5"]);
+        assert_eq!(errors[2].len(), 1);
+        assert!(errors[2][0].starts_with("output is not valid JSON"));
+    }
+}
@@ -0,0 +1,375 @@
+//! JSON Schema validation of structured outputs.
+//!
+//! Covers the keywords structured-output schemas are written with: `type`,
+//! `enum`, `const`, `properties`, `required`, `additionalProperties`,
+//! `items`, `minItems`/`maxItems`, `uniqueItems`, `minLength`/`maxLength`,
+//! `minimum`/`maximum` (and the exclusive forms), `multipleOf`, `allOf`,
+//! `anyOf`, `oneOf`, `not` and local `$ref`s (`#/$defs/...`). Other
+//! keywords, `pattern` and `format` included, are not checked;
+//! `unsupported_keywords` lists the ones a schema uses so they can be
+//! reported rather than silently passed.
+
+use serde_json::Value;
+
+/// Keywords `check` enforces
+const SUPPORTED: &[&str] = &[
+    "type", "enum", "const", "properties", "required", "additionalProperties", "items", "minItems", "maxItems",
+    "uniqueItems", "minLength", "maxLength", "minimum", "maximum", "exclusiveMinimum", "exclusiveMaximum",
+    "multipleOf", "allOf", "anyOf", "oneOf", "not", "$ref",
+];
+/// Keywords that don't constrain the output, so there is nothing to check
+const ANNOTATIONS: &[&str] = &[
+    "$schema", "$id", "$comment", "$defs", "definitions", "title", "description", "default", "examples",
+    "deprecated", "readOnly", "writeOnly",
+];
+/// `$ref`s followed in a row without moving into the output; more means the
+/// schema refers to itself without ever constraining anything
+const MAX_REF_HOPS: usize = 32;
+
+/// Check a model's output against `schema`. Returns one message per
+/// violation, empty when the output conforms.
+pub fn check_output(schema: &Value, content: &str) -> Vec<String> {
+    match parse_output(content) {
+        Ok(value) => validate(schema, &value),
+        Err(e) => vec![e],
+    }
+}
+
+/// Parse an output as JSON. A reply that is a single fenced code block
+/// (as models without a native JSON mode tend to send) is unwrapped first.
+pub fn parse_output(content: &str) -> Result<Value, String> {
+    let mut text = content.trim();
+    if let Some(fenced) = text.strip_prefix("```").and_then(|t| t.strip_suffix("```")) {
+        // Drop the language tag on the opening line
+        text = fenced.split_once('\n').map_or(fenced, |(_, body)| body).trim();
+    }
+    serde_json::from_str(text).map_err(|e| format!("output is not valid JSON: {}", e))
+}
+
+/// Errors of `instance` against `schema`, each prefixed with where in the
+/// instance it occurred (`$` is the whole output)
+pub fn validate(schema: &Value, instance: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    check(schema, schema, instance, "$", 0, &mut errors);
+    errors
+}
+
+/// Keywords of `schema` (and its subschemas) that `validate` doesn't check,
+/// sorted and without repeats
+pub fn unsupported_keywords(schema: &Value) -> Vec<String> {
+    let mut found = Vec::new();
+    collect_unsupported(schema, &mut found);
+    found.sort();
+    found.dedup();
+    found
+}
+
+fn collect_unsupported(schema: &Value, found: &mut Vec<String>) {
+    let Value::Object(schema) = schema else {
+        return;
+    };
+    for (keyword, value) in schema {
+        match keyword.as_str() {
+            "properties" | "$defs" | "definitions" => {
+                for subschema in value.as_object().into_iter().flat_map(|map| map.values()) {
+                    collect_unsupported(subschema, found);
+                }
+            }
+            "allOf" | "anyOf" | "oneOf" => {
+                for subschema in value.as_array().into_iter().flatten() {
+                    collect_unsupported(subschema, found);
+                }
+            }
+            "items" | "additionalProperties" | "not" => collect_unsupported(value, found),
+            keyword if SUPPORTED.contains(&keyword) || ANNOTATIONS.contains(&keyword) => {}
+            keyword => found.push(keyword.to_string()),
+        }
+    }
+}
+
+/// `hops` counts the `$ref`s followed since the last step into the instance
+fn check(root: &Value, schema: &Value, instance: &Value, path: &str, hops: usize, errors: &mut Vec<String>) {
+    let schema = match schema {
+        Value::Bool(true) => return,
+        Value::Bool(false) => return errors.push(format!("{}: no value is allowed here", path)),
+        Value::Object(schema) => schema,
+        _ => return,
+    };
+
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        match reference.strip_prefix('#').and_then(|pointer| root.pointer(pointer)) {
+            Some(_) if hops >= MAX_REF_HOPS => {
+                return errors.push(format!("{}: $ref {} refers back to itself", path, reference));
+            }
+            Some(target) => check(root, target, instance, path, hops + 1, errors),
+            None => errors.push(format!("{}: cannot resolve $ref {}", path, reference)),
+        }
+    }
+
+    if let Some(types) = schema.get("type") {
+        let allowed: Vec<&str> = match types {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| is_type(instance, t)) {
+            // Nothing else is meaningful once the type is wrong
+            return errors.push(format!("{}: expected {}, got {}", path, allowed.join(" or "), type_name(instance)));
+        }
+    }
+    if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+        if !options.iter().any(|option| equal(option, instance)) {
+            let options: Vec<String> = options.iter().map(Value::to_string).collect();
+            errors.push(format!("{}: {} is not one of {}", path, instance, options.join(", ")));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if !equal(expected, instance) {
+            errors.push(format!("{}: expected {}, got {}", path, expected, instance));
+        }
+    }
+
+    match instance {
+        Value::String(text) => {
+            let length = text.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(Value::as_u64).filter(|&min| length < min) {
+                errors.push(format!("{}: shorter than {} characters", path, min));
+            }
+            if let Some(max) = schema.get("maxLength").and_then(Value::as_u64).filter(|&max| length > max) {
+                errors.push(format!("{}: longer than {} characters", path, max));
+            }
+        }
+        Value::Number(number) => check_number(schema, number.as_f64().unwrap_or_default(), path, errors),
+        Value::Array(items) => {
+            let count = items.len() as u64;
+            if let Some(min) = schema.get("minItems").and_then(Value::as_u64).filter(|&min| count < min) {
+                errors.push(format!("{}: fewer than {} items", path, min));
+            }
+            if let Some(max) = schema.get("maxItems").and_then(Value::as_u64).filter(|&max| count > max) {
+                errors.push(format!("{}: more than {} items", path, max));
+            }
+            if schema.get("uniqueItems") == Some(&Value::Bool(true)) {
+                let duplicate = items.iter().enumerate().any(|(i, a)| items[..i].iter().any(|b| equal(a, b)));
+                if duplicate {
+                    errors.push(format!("{}: items are not unique", path));
+                }
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    check(root, item_schema, item, &format!("{}[{}]", path, index), 0, errors);
+                }
+            }
+        }
+        Value::Object(object) => {
+            for name in schema.get("required").and_then(Value::as_array).into_iter().flatten() {
+                if let Some(name) = name.as_str().filter(|name| !object.contains_key(*name)) {
+                    errors.push(format!("{}: missing required property {}", path, name));
+                }
+            }
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (key, value) in object {
+                let key_path = format!("{}.{}", path, key);
+                match properties.and_then(|p| p.get(key)) {
+                    Some(property) => check(root, property, value, &key_path, 0, errors),
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => errors.push(format!("{}: unexpected property {}", path, key)),
+                        Some(additional) => check(root, additional, value, &key_path, 0, errors),
+                        None => {}
+                    },
+                }
+            }
+        }
+        _ => {}
+    }
+
+    for branch in schema.get("allOf").and_then(Value::as_array).into_iter().flatten() {
+        check(root, branch, instance, path, hops, errors);
+    }
+    if let Some(branches) = schema.get("anyOf").and_then(Value::as_array) {
+        if !branches.iter().any(|branch| conforms(root, branch, instance, hops)) {
+            errors.push(format!("{}: does not match any of the allowed schemas", path));
+        }
+    }
+    if let Some(branches) = schema.get("oneOf").and_then(Value::as_array) {
+        let matching = branches.iter().filter(|branch| conforms(root, branch, instance, hops)).count();
+        if matching != 1 {
+            errors.push(format!("{}: matches {} of the oneOf schemas, expected exactly 1", path, matching));
+        }
+    }
+    if let Some(not) = schema.get("not") {
+        if conforms(root, not, instance, hops) {
+            errors.push(format!("{}: matches a schema it must not match", path));
+        }
+    }
+}
+
+fn check_number(schema: &serde_json::Map<String, Value>, value: f64, path: &str, errors: &mut Vec<String>) {
+    let bound = |key: &str| schema.get(key).and_then(Value::as_f64);
+    if let Some(min) = bound("minimum").filter(|&min| value < min) {
+        errors.push(format!("{}: {} is less than {}", path, value, min));
+    }
+    if let Some(max) = bound("maximum").filter(|&max| value > max) {
+        errors.push(format!("{}: {} is greater than {}", path, value, max));
+    }
+    if let Some(min) = bound("exclusiveMinimum").filter(|&min| value <= min) {
+        errors.push(format!("{}: {} is not greater than {}", path, value, min));
+    }
+    if let Some(max) = bound("exclusiveMaximum").filter(|&max| value >= max) {
+        errors.push(format!("{}: {} is not less than {}", path, value, max));
+    }
+    if let Some(step) = bound("multipleOf").filter(|&step| step > 0.0 && (value / step).fract() != 0.0) {
+        errors.push(format!("{}: {} is not a multiple of {}", path, value, step));
+    }
+}
+
+fn conforms(root: &Value, schema: &Value, instance: &Value, hops: usize) -> bool {
+    let mut errors = Vec::new();
+    check(root, schema, instance, "$", hops, &mut errors);
+    errors.is_empty()
+}
+
+fn is_type(value: &Value, name: &str) -> bool {
+    match name {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "number" => value.is_number(),
+        "integer" => value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        "string" => value.is_string(),
+        _ => false,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// JSON equality, with numbers compared by value (`1` equals `1.0`)
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        (Value::Array(a), Value::Array(b)) => a.len() == b.len() && a.iter().zip(b).all(|(a, b)| equal(a, b)),
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len() && a.iter().all(|(key, a)| b.get(key).is_some_and(|b| equal(a, b)))
+        }
+        (a, b) => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn review_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "sentiment": { "enum": ["positive", "negative", "neutral"] },
+                "score": { "type": "integer", "minimum": 1, "maximum": 5 },
+                "tags": { "type": "array", "items": { "$ref": "#/$defs/tag" }, "maxItems": 3 }
+            },
+            "required": ["sentiment", "score"],
+            "additionalProperties": false,
+            "$defs": { "tag": { "type": "string", "minLength": 1 } }
+        })
+    }
+
+    #[test]
+    fn test_conforming_output() {
+        let output = r#"{"sentiment": "positive", "score": 5, "tags": ["price", "quality"]}"#;
+        assert_eq!(check_output(&review_schema(), output), Vec::<String>::new());
+        // 4.0 is an integer by value
+        assert!(validate(&review_schema(), &json!({ "sentiment": "neutral", "score": 4.0 })).is_empty());
+    }
+
+    #[test]
+    fn test_violations_name_their_location() {
+        let output = json!({ "sentiment": "angry", "score": 9, "tags": ["ok", 3, ""], "extra": true });
+        // Sorted: property order depends on serde_json's map features
+        let mut errors = validate(&review_schema(), &output);
+        errors.sort();
+        assert_eq!(
+            errors,
+            vec![
+                "$.score: 9 is greater than 5",
+                "$.sentiment: \"angry\" is not one of \"positive\", \"negative\", \"neutral\"",
+                "$.tags[1]: expected string, got number",
+                "$.tags[2]: shorter than 1 characters",
+                "$: unexpected property extra",
+            ]
+        );
+        assert_eq!(
+            validate(&review_schema(), &json!({ "score": "5" })),
+            vec!["$: missing required property sentiment", "$.score: expected integer, got string"]
+        );
+    }
+
+    #[test]
+    fn test_output_parsing() {
+        assert_eq!(parse_output("```json\n{\"a\": 1}\n```").unwrap(), json!({ "a": 1 }));
+        assert_eq!(parse_output("  [1, 2]\n").unwrap(), json!([1, 2]));
+        let errors = check_output(&review_schema(), "Sure! Here is the JSON: {}");
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("output is not valid JSON"), "{}", errors[0]);
+    }
+
+    #[test]
+    fn test_combinators() {
+        let schema = json!({ "anyOf": [{ "type": "string" }, { "type": "null" }] });
+        assert!(validate(&schema, &Value::Null).is_empty());
+        assert_eq!(validate(&schema, &json!(1)), vec!["$: does not match any of the allowed schemas"]);
+
+        let schema = json!({ "oneOf": [{ "type": "number" }, { "type": "integer" }] });
+        assert_eq!(validate(&schema, &json!(2)), vec!["$: matches 2 of the oneOf schemas, expected exactly 1"]);
+        assert!(validate(&schema, &json!(2.5)).is_empty());
+    }
+
+    #[test]
+    fn test_self_referential_refs() {
+        // Recursion through the output is fine
+        let tree = json!({
+            "$defs": {
+                "node": {
+                    "type": "object",
+                    "properties": { "children": { "type": "array", "items": { "$ref": "#/$defs/node" } } }
+                }
+            },
+            "$ref": "#/$defs/node"
+        });
+        assert!(validate(&tree, &json!({ "children": [{ "children": [{}] }] })).is_empty());
+
+        // A loop that never reaches the output is reported, not a stack overflow
+        let schema = json!({
+            "$defs": { "a": { "$ref": "#/$defs/b" }, "b": { "anyOf": [{ "$ref": "#/$defs/a" }] } },
+            "$ref": "#/$defs/a"
+        });
+        assert_eq!(validate(&schema, &json!(1)), vec!["$: does not match any of the allowed schemas"]);
+        assert_eq!(validate(&json!({ "$ref": "#" }), &json!(1)), vec!["$: $ref # refers back to itself"]);
+    }
+
+    #[test]
+    fn test_unsupported_keywords_are_listed() {
+        let schema = json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "type": "object",
+            "description": "A user",
+            "properties": {
+                "email": { "type": "string", "format": "email" },
+                "id": { "type": "string", "pattern": "^[a-z]+$" },
+                "tags": { "type": "array", "items": { "type": "string", "pattern": "^#" } }
+            },
+            "dependentRequired": { "email": ["id"] }
+        });
+        assert_eq!(unsupported_keywords(&schema), vec!["dependentRequired", "format", "pattern"]);
+        assert!(unsupported_keywords(&review_schema()).is_empty());
+    }
+}
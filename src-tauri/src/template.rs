@@ -0,0 +1,131 @@
+//! `{{variable}}` rendering for prompt versions.
+//!
+//! Syntax, a superset of what the GUI substitutes:
+//! - `{{ name }}` inserts the dataset value for `name`
+//! - `{{ tone|neutral }}` falls back to `neutral` when `tone` is missing or empty
+//! - `\{{` produces a literal `{{`
+//! - `{{- name }}` / `{{ name -}}` trim the whitespace before / after the tag
+//!
+//! Anything between braces that isn't a variable name (e.g. `{{ }}` or a
+//! JSON snippet) is left as written.
+
+use std::collections::HashMap;
+
+/// Output of a render, with the variables that had neither a value nor a default
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rendered {
+    pub text: String,
+    pub missing: Vec<String>,
+}
+
+/// Render `template`. Missing variables without a default keep their
+/// placeholder (as in the GUI) and are listed in `missing`.
+pub fn render(template: &str, variables: &HashMap<String, String>) -> Rendered {
+    let mut text = String::with_capacity(template.len());
+    let mut missing: Vec<String> = Vec::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        // Escaped opening braces
+        if rest[..start].ends_with('\\') {
+            text.push_str(&rest[..start - 1]);
+            text.push_str("{{");
+            rest = &rest[start + 2..];
+            continue;
+        }
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let tag = &rest[start..start + 2 + len + 2];
+        let inner = &tag[2..tag.len() - 2];
+
+        let (trim_before, inner) = match inner.strip_prefix('-') {
+            Some(inner) => (true, inner),
+            None => (false, inner),
+        };
+        let (trim_after, inner) = match inner.strip_suffix('-') {
+            Some(inner) => (true, inner),
+            None => (false, inner),
+        };
+        let (name, default) = match inner.split_once('|') {
+            Some((name, default)) => (name.trim(), Some(default.trim())),
+            None => (inner.trim(), None),
+        };
+
+        if !is_variable_name(name) {
+            text.push_str(&rest[..start + 2]);
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        text.push_str(&rest[..start]);
+        if trim_before {
+            text.truncate(text.trim_end().len());
+        }
+        match variables.get(name).filter(|v| !v.is_empty()) {
+            Some(value) => text.push_str(value),
+            None => match default {
+                Some(default) => text.push_str(default),
+                None => {
+                    if !missing.iter().any(|m| m == name) {
+                        missing.push(name.to_string());
+                    }
+                    text.push_str(tag);
+                }
+            },
+        }
+        rest = &rest[start + tag.len()..];
+        if trim_after {
+            rest = rest.trim_start();
+        }
+    }
+    text.push_str(rest);
+
+    Rendered { text, missing }
+}
+
+fn is_variable_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_lenient_matches_gui_substitution() {
+        let rendered = render(
+            "Hi {{name}}, {{ name }}! {{empty}} {{other}}",
+            &vars(&[("name", "Alice"), ("empty", "")]),
+        );
+        assert_eq!(rendered.text, "Hi Alice, Alice! {{empty}} {{other}}");
+        assert_eq!(rendered.missing, vec!["empty".to_string(), "other".to_string()]);
+    }
+
+    #[test]
+    fn test_defaults() {
+        let rendered = render("Tone: {{tone|neutral}}, {{ style | very formal }}", &vars(&[]));
+        assert_eq!(rendered.text, "Tone: neutral, very formal");
+        assert!(rendered.missing.is_empty());
+
+        let rendered = render("Tone: {{tone|neutral}}", &vars(&[("tone", "playful")]));
+        assert_eq!(rendered.text, "Tone: playful");
+    }
+
+    #[test]
+    fn test_escaping_and_non_variables() {
+        let rendered = render(r#"Literal \{{name}} and {{ }} and {{"a": 1}}"#, &vars(&[("name", "x")]));
+        assert_eq!(rendered.text, r#"Literal {{name}} and {{ }} and {{"a": 1}}"#);
+        assert!(rendered.missing.is_empty());
+    }
+
+    #[test]
+    fn test_whitespace_control() {
+        let rendered = render("Items:\n  {{- list -}}\n\nEnd", &vars(&[("list", " a, b")]));
+        assert_eq!(rendered.text, "Items: a, bEnd");
+    }
+}
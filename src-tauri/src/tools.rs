@@ -0,0 +1,196 @@
+//! Tool (function) calling: the definitions a prompt version offers the
+//! model, the calls it makes, and the assertions that grade them against
+//! what a dataset item expects.
+//!
+//! Expected arguments match as a subset: every key the item lists must be
+//! present with an equal value, keys it doesn't mention are ignored. Calls
+//! the item doesn't expect are not a failure, except when it expects none.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A function the model may call. `parameters` is a JSON Schema object.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolDefinition {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub parameters: Value,
+}
+
+/// A call the model made. Arguments the provider sent as text that isn't
+/// valid JSON are kept as a string.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub name: String,
+    #[serde(default)]
+    pub arguments: Value,
+}
+
+/// A call a dataset item expects; without `arguments` only the name is checked
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExpectedToolCall {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<Value>,
+}
+
+/// Outcome of one check on a result
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AssertionResult {
+    /// `tool_name`, `tool_arguments` or `no_tool_call`
+    pub kind: String,
+    pub passed: bool,
+    pub message: String,
+}
+
+impl AssertionResult {
+    fn new(kind: &str, passed: bool, message: String) -> Self {
+        Self { kind: kind.to_string(), passed, message }
+    }
+}
+
+/// Parse the JSON-encoded arguments OpenAI-style APIs send. An empty
+/// string is no arguments; text that isn't JSON is kept as is.
+pub fn parse_arguments(raw: &str) -> Value {
+    if raw.trim().is_empty() {
+        return Value::Object(Default::default());
+    }
+    serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()))
+}
+
+/// Grade the calls a model made against the ones an item expects. Each
+/// expected call is matched, in order, to the first unclaimed call of the
+/// same name. An empty `expected` asserts that no tool was called.
+pub fn check_tool_calls(expected: &[ExpectedToolCall], actual: &[ToolCall]) -> Vec<AssertionResult> {
+    if expected.is_empty() {
+        let message = if actual.is_empty() {
+            "no tool called".to_string()
+        } else {
+            format!("expected no tool call, got {}", call_names(actual))
+        };
+        return vec![AssertionResult::new("no_tool_call", actual.is_empty(), message)];
+    }
+
+    let mut claimed = vec![false; actual.len()];
+    let mut results = Vec::new();
+    for want in expected {
+        let found = actual
+            .iter()
+            .enumerate()
+            .position(|(i, call)| !claimed[i] && call.name == want.name);
+        let Some(index) = found else {
+            let got = if actual.is_empty() { "no tool call".to_string() } else { call_names(actual) };
+            results.push(AssertionResult::new(
+                "tool_name",
+                false,
+                format!("expected a call to {}, got {}", want.name, got),
+            ));
+            continue;
+        };
+        claimed[index] = true;
+        results.push(AssertionResult::new("tool_name", true, format!("called {}", want.name)));
+
+        if let Some(arguments) = &want.arguments {
+            let mut mismatches = Vec::new();
+            compare(arguments, &actual[index].arguments, "", &mut mismatches);
+            let message = if mismatches.is_empty() {
+                format!("{} arguments match", want.name)
+            } else {
+                format!("{} arguments: {}", want.name, mismatches.join("; "))
+            };
+            results.push(AssertionResult::new("tool_arguments", mismatches.is_empty(), message));
+        }
+    }
+    results
+}
+
+fn call_names(calls: &[ToolCall]) -> String {
+    calls.iter().map(|c| c.name.as_str()).collect::<Vec<_>>().join(", ")
+}
+
+/// Collect where `actual` differs from `expected`. Objects match as a
+/// subset, anything else must be equal (numbers by value, so `1` equals `1.0`).
+fn compare(expected: &Value, actual: &Value, path: &str, mismatches: &mut Vec<String>) {
+    match (expected, actual) {
+        (Value::Object(want), Value::Object(got)) => {
+            for (key, want) in want {
+                let path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                match got.get(key) {
+                    Some(got) => compare(want, got, &path, mismatches),
+                    None => mismatches.push(format!("missing {}", path)),
+                }
+            }
+        }
+        (Value::Number(want), Value::Number(got)) if want.as_f64() == got.as_f64() => {}
+        (want, got) if want == got => {}
+        (want, got) => {
+            let path = if path.is_empty() { "arguments" } else { path };
+            mismatches.push(format!("{} is {}, expected {}", path, got, want));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn call(name: &str, arguments: Value) -> ToolCall {
+        ToolCall { id: None, name: name.to_string(), arguments }
+    }
+
+    fn expect(name: &str, arguments: Option<Value>) -> ExpectedToolCall {
+        ExpectedToolCall { name: name.to_string(), arguments }
+    }
+
+    #[test]
+    fn test_arguments_match_as_subset() {
+        let actual = vec![call("get_weather", json!({ "city": "Paris", "units": "celsius", "days": 3.0 }))];
+        let results = check_tool_calls(&[expect("get_weather", Some(json!({ "city": "Paris", "days": 3 })))], &actual);
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.passed), "{:?}", results);
+
+        let results = check_tool_calls(
+            &[expect("get_weather", Some(json!({ "city": "Lyon", "country": "FR" })))],
+            &actual,
+        );
+        assert!(results[0].passed);
+        assert!(!results[1].passed);
+        assert_eq!(results[1].message, "get_weather arguments: city is \"Paris\", expected \"Lyon\"; missing country");
+    }
+
+    #[test]
+    fn test_wrong_tool_and_repeated_calls() {
+        let actual = vec![call("search", json!({})), call("search", json!({ "q": "b" }))];
+        let results = check_tool_calls(&[expect("get_weather", None)], &actual);
+        assert_eq!(results[0].kind, "tool_name");
+        assert_eq!(results[0].message, "expected a call to get_weather, got search, search");
+
+        // Each expected call claims its own actual call
+        let results = check_tool_calls(
+            &[expect("search", None), expect("search", Some(json!({ "q": "b" }))), expect("search", None)],
+            &actual,
+        );
+        let passed: Vec<bool> = results.iter().map(|r| r.passed).collect();
+        assert_eq!(passed, vec![true, true, true, false]);
+    }
+
+    #[test]
+    fn test_expecting_no_tool_call() {
+        assert!(check_tool_calls(&[], &[])[0].passed);
+        let results = check_tool_calls(&[], &[call("search", json!({}))]);
+        assert_eq!(results[0].kind, "no_tool_call");
+        assert!(!results[0].passed);
+    }
+
+    #[test]
+    fn test_parse_arguments() {
+        assert_eq!(parse_arguments(r#"{"city":"Paris"}"#), json!({ "city": "Paris" }));
+        assert_eq!(parse_arguments(""), json!({}));
+        assert_eq!(parse_arguments("{\"city\":"), json!("{\"city\":"));
+    }
+}